@@ -1,3 +1,4 @@
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Action {
     ToggleFrameStatistics,
     ToggleLiveTypingStatistics,
@@ -10,5 +11,53 @@ pub enum Action {
     PreviousCorpus,
     IncreaseTestLength,
     DecreaseTestLength,
+    ToggleTestMode,
+    ToggleWordByWord,
+    NextStrictness,
+    NextSamplingMethod,
+    NextTheme,
+    ToggleGhost,
+    ToggleHistory,
+    NextHistorySort,
+    ReverseHistorySort,
+    NextHistoryCorpus,
+    PreviousHistoryCorpus,
+    SelectPreviousHistoryEntry,
+    SelectNextHistoryEntry,
+    ToggleHeatmap,
+    NextKeyboardLayout,
+    PreviousKeyboardLayout,
+    NextHeatmapMetric,
+    NextResultsTab,
+    PreviousResultsTab,
+    DismissResults,
+    ReplayTest,
+    RaceTest,
+    ExportResults,
+    StartRace,
+    ToggleReplayPause,
+    StepReplayForward,
+    StepReplayBackward,
+    IncreaseReplaySpeed,
+    DecreaseReplaySpeed,
+    RestartReplay,
+    CloseReplay,
+    Resize,
     Quit,
 }
+
+/// An [`Action`] together with the moment its terminal event was read, so that keystroke
+/// timings do not depend on how long the previous frame took to draw.
+pub struct TimedAction {
+    pub action: Action,
+    pub time: std::time::Instant,
+}
+
+impl TimedAction {
+    pub fn now(action: Action) -> Self {
+        TimedAction {
+            action,
+            time: crate::clock::now(),
+        }
+    }
+}
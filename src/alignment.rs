@@ -0,0 +1,331 @@
+use serde::{Deserialize, Serialize};
+
+/// How a typed grapheme, or pair of graphemes, deviates from the target.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorKind {
+    /// A grapheme was typed in place of another one.
+    Substitution,
+    /// A grapheme was typed that is not in the target.
+    Insertion,
+    /// A grapheme of the target was skipped.
+    Omission,
+    /// Two adjacent graphemes of the target were typed in reverse order.
+    Transposition,
+}
+
+/// One step of an [`Alignment`], pairing a range of the target with a range of the typed text.
+/// Both ranges are grapheme indices, one of them is empty for insertions and omissions.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Step {
+    /// `None` if the typed graphemes match the target.
+    pub error: Option<ErrorKind>,
+    pub target: std::ops::Range<usize>,
+    pub typed: std::ops::Range<usize>,
+}
+
+/// Number of errors of each kind in a test.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ErrorCounts {
+    pub substitutions: usize,
+    pub insertions: usize,
+    pub omissions: usize,
+    pub transpositions: usize,
+}
+
+impl ErrorCounts {
+    pub fn total(&self) -> usize {
+        self.substitutions + self.insertions + self.omissions + self.transpositions
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Alignment {
+    pub steps: Vec<Step>,
+}
+
+impl Alignment {
+    /// End of the part of the target the typed text was aligned with, that is the position of
+    /// the next grapheme to type.
+    pub fn target_end(&self) -> usize {
+        self.steps.last().map_or(0, |step| step.target.end)
+    }
+
+    /// Number of typed graphemes that match the target.
+    pub fn correct_graphemes(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|step| step.error.is_none())
+            .map(|step| step.typed.len())
+            .sum()
+    }
+
+    pub fn error_counts(&self) -> ErrorCounts {
+        let mut counts = ErrorCounts::default();
+        for step in &self.steps {
+            match step.error {
+                Some(ErrorKind::Substitution) => counts.substitutions += 1,
+                Some(ErrorKind::Insertion) => counts.insertions += 1,
+                Some(ErrorKind::Omission) => counts.omissions += 1,
+                Some(ErrorKind::Transposition) => counts.transpositions += 1,
+                None => {}
+            }
+        }
+        counts
+    }
+
+    /// The step that accounts for the typed grapheme at `typed_position`.
+    pub fn step_for_typed(&self, typed_position: usize) -> Option<&Step> {
+        self.steps
+            .iter()
+            .find(|step| step.typed.contains(&typed_position))
+    }
+
+    /// Appends `other`, whose ranges start at zero, shifted to the given offsets.
+    pub fn extend_shifted(&mut self, other: Alignment, target_offset: usize, typed_offset: usize) {
+        self.steps.extend(other.steps.into_iter().map(|step| Step {
+            error: step.error,
+            target: step.target.start + target_offset..step.target.end + target_offset,
+            typed: step.typed.start + typed_offset..step.typed.end + typed_offset,
+        }));
+    }
+}
+
+/// How far the typed text may drift from the target, in graphemes, beyond the difference of their
+/// lengths, before the alignment gives up on pairing them. Keeps aligning long timed tests cheap
+/// enough to do on every keystroke.
+const MAX_DRIFT: usize = 24;
+
+/// Aligns `typed` with `target` at the smallest number of substitutions, insertions, omissions
+/// and transpositions, each counting as one error.
+///
+/// If `is_complete` is false, the typist is still typing and only a prefix of the target is
+/// aligned: the graphemes past what was typed are not omissions, just not typed yet.
+pub fn align(target: &[&str], typed: &[&str], is_complete: bool) -> Alignment {
+    // The band has to reach the end of whatever part of the target the typed text is aligned
+    // with. That is the whole target for complete tests, and at most all of it otherwise.
+    let drift = MAX_DRIFT
+        + if is_complete {
+            typed.len().abs_diff(target.len())
+        } else {
+            typed.len().saturating_sub(target.len())
+        };
+    let table = Table::new(target, typed, drift);
+
+    let target_end = if is_complete {
+        target.len()
+    } else {
+        // Among equally good prefixes, the one as long as the typed text is the most natural,
+        // so that a wrong last grapheme reads as a substitution rather than an insertion.
+        let band_start = typed.len().saturating_sub(drift);
+        let band_end = std::cmp::min(typed.len() + drift, target.len());
+        (band_start..=band_end)
+            .min_by_key(|&j| (table.get(typed.len(), j), j.abs_diff(typed.len())))
+            .unwrap_or(0)
+    };
+
+    table.backtrack(target, typed, target_end)
+}
+
+/// Edit distances between prefixes of the typed text and prefixes of the target, only kept
+/// within `drift` of the diagonal.
+struct Table {
+    drift: usize,
+    width: usize,
+    costs: Vec<usize>,
+}
+
+impl Table {
+    const UNREACHABLE: usize = usize::MAX / 2;
+
+    fn new(target: &[&str], typed: &[&str], drift: usize) -> Self {
+        let width = 2 * drift + 1;
+        let mut table = Table {
+            drift,
+            width,
+            costs: vec![Self::UNREACHABLE; (typed.len() + 1) * width],
+        };
+
+        for i in 0..=typed.len() {
+            let band_start = i.saturating_sub(drift);
+            let band_end = std::cmp::min(i + drift, target.len());
+            for j in band_start..=band_end {
+                let cost = if i == 0 {
+                    j
+                } else if j == 0 {
+                    i
+                } else {
+                    let mut cost = std::cmp::min(
+                        table.get(i - 1, j - 1) + usize::from(typed[i - 1] != target[j - 1]),
+                        std::cmp::min(table.get(i - 1, j), table.get(i, j - 1)) + 1,
+                    );
+                    if Self::is_transposition(target, typed, i, j) {
+                        cost = std::cmp::min(cost, table.get(i - 2, j - 2) + 1);
+                    }
+                    cost
+                };
+                table.set(i, j, cost);
+            }
+        }
+
+        table
+    }
+
+    fn is_transposition(target: &[&str], typed: &[&str], i: usize, j: usize) -> bool {
+        i >= 2
+            && j >= 2
+            && typed[i - 1] == target[j - 2]
+            && typed[i - 2] == target[j - 1]
+            && typed[i - 1] != typed[i - 2]
+    }
+
+    fn index(&self, i: usize, j: usize) -> Option<usize> {
+        let offset = (j + self.drift).checked_sub(i)?;
+        (offset < self.width).then_some(i * self.width + offset)
+    }
+
+    fn get(&self, i: usize, j: usize) -> usize {
+        self.index(i, j)
+            .and_then(|index| self.costs.get(index))
+            .copied()
+            .unwrap_or(Self::UNREACHABLE)
+    }
+
+    fn set(&mut self, i: usize, j: usize, cost: usize) {
+        if let Some(index) = self.index(i, j) {
+            self.costs[index] = cost;
+        }
+    }
+
+    /// Walks back from the end of `typed` and `target[..target_end]`, preferring matches and
+    /// substitutions over transpositions, insertions and omissions when they are equally good.
+    fn backtrack(&self, target: &[&str], typed: &[&str], target_end: usize) -> Alignment {
+        let mut steps = Vec::new();
+        let (mut i, mut j) = (typed.len(), target_end);
+
+        while i > 0 || j > 0 {
+            let cost = self.get(i, j);
+
+            let step = if i > 0
+                && j > 0
+                && cost == self.get(i - 1, j - 1) + usize::from(typed[i - 1] != target[j - 1])
+            {
+                let error = (typed[i - 1] != target[j - 1]).then_some(ErrorKind::Substitution);
+                (error, 1, 1)
+            } else if Self::is_transposition(target, typed, i, j)
+                && cost == self.get(i - 2, j - 2) + 1
+            {
+                (Some(ErrorKind::Transposition), 2, 2)
+            } else if i > 0 && (j == 0 || cost == self.get(i - 1, j) + 1) {
+                (Some(ErrorKind::Insertion), 0, 1)
+            } else {
+                (Some(ErrorKind::Omission), 1, 0)
+            };
+
+            let (error, target_length, typed_length) = step;
+            steps.push(Step {
+                error,
+                target: j - target_length..j,
+                typed: i - typed_length..i,
+            });
+            i -= typed_length;
+            j -= target_length;
+        }
+
+        steps.reverse();
+        Alignment { steps }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graphemes(text: &str) -> Vec<&str> {
+        unicode_segmentation::UnicodeSegmentation::graphemes(text, true).collect()
+    }
+
+    fn align_text(target: &str, typed: &str, is_complete: bool) -> Alignment {
+        align(&graphemes(target), &graphemes(typed), is_complete)
+    }
+
+    fn errors(alignment: &Alignment) -> Vec<ErrorKind> {
+        alignment
+            .steps
+            .iter()
+            .filter_map(|step| step.error)
+            .collect()
+    }
+
+    #[test]
+    fn matching_text_has_no_errors() {
+        let alignment = align_text("hello", "hello", true);
+        assert_eq!(alignment.correct_graphemes(), 5);
+        assert_eq!(alignment.error_counts().total(), 0);
+        assert_eq!(alignment.target_end(), 5);
+    }
+
+    #[test]
+    fn substitution() {
+        let alignment = align_text("hello", "hallo", true);
+        assert_eq!(errors(&alignment), [ErrorKind::Substitution]);
+        assert_eq!(alignment.step_for_typed(1).unwrap().target, 1..2);
+        assert_eq!(alignment.correct_graphemes(), 4);
+    }
+
+    #[test]
+    fn insertion() {
+        let alignment = align_text("hello", "heello", true);
+        assert_eq!(errors(&alignment), [ErrorKind::Insertion]);
+        assert_eq!(alignment.correct_graphemes(), 5);
+    }
+
+    #[test]
+    fn omission() {
+        let alignment = align_text("hello", "helo", true);
+        assert_eq!(errors(&alignment), [ErrorKind::Omission]);
+        assert_eq!(alignment.correct_graphemes(), 4);
+    }
+
+    #[test]
+    fn transposition() {
+        let alignment = align_text("hello", "hlelo", true);
+        assert_eq!(errors(&alignment), [ErrorKind::Transposition]);
+        let step = alignment.step_for_typed(1).unwrap();
+        assert_eq!((step.target.clone(), step.typed.clone()), (1..3, 1..3));
+    }
+
+    #[test]
+    fn incomplete_text_is_aligned_with_a_prefix() {
+        let alignment = align_text("hello world", "hel", false);
+        assert_eq!(alignment.target_end(), 3);
+        assert_eq!(alignment.error_counts().total(), 0);
+
+        // A wrong last grapheme is a substitution, not an insertion before the rest.
+        let alignment = align_text("hello world", "hex", false);
+        assert_eq!(errors(&alignment), [ErrorKind::Substitution]);
+        assert_eq!(alignment.target_end(), 3);
+    }
+
+    #[test]
+    fn typed_text_far_longer_than_the_target() {
+        let typed = format!("ab{}", "x".repeat(34));
+
+        let alignment = align_text("ab", &typed, true);
+        assert_eq!(alignment.correct_graphemes(), 2);
+        assert_eq!(alignment.error_counts().insertions, 34);
+        assert_eq!(alignment.error_counts().omissions, 0);
+
+        let alignment = align_text("ab", &typed, false);
+        assert_eq!(alignment.target_end(), 2);
+        assert_eq!(alignment.correct_graphemes(), 2);
+        assert_eq!(alignment.error_counts().insertions, 34);
+    }
+
+    #[test]
+    fn target_far_longer_than_the_typed_text() {
+        let target = format!("ab{}", "c".repeat(40));
+        let alignment = align_text(&target, "ab", true);
+        assert_eq!(alignment.correct_graphemes(), 2);
+        assert_eq!(alignment.error_counts().omissions, 40);
+    }
+}
@@ -0,0 +1,417 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::{
+    corpus::{Corpus, CorpusId, CorpusKind, SamplingMethod},
+    export::{Export, ExportFormat},
+    history::{format_timestamp, History, HistoryBrowser, HistorySort},
+    model::{Config, Strictness, TestMode},
+    race::{Address, DEFAULT_ADDRESS},
+    test_code::TestCode,
+    theme::Theme,
+};
+
+#[derive(Parser)]
+#[command(name = "ttl", version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Start typing (the default when no subcommand is given)
+    Run(RunArgs),
+    /// Print the results of past tests
+    History(HistoryArgs),
+    /// Write the results of past tests as CSV or JSON, see the README for their fields
+    Export(ExportArgs),
+    /// Inspect the available corpora
+    Corpora {
+        #[command(subcommand)]
+        command: CorporaCommand,
+    },
+    /// Replay a script of keystrokes without a terminal, checking what the screens show
+    Script(ScriptArgs),
+    /// Race other typists on the same machine or network
+    Race {
+        #[command(subcommand)]
+        command: RaceCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RaceCommand {
+    /// Host a race that others can join, picking the text of every race
+    #[command(mut_arg("code", |arg| arg.hide(true)))]
+    Host(HostArgs),
+    /// Join a race hosted by someone else
+    Join(JoinArgs),
+}
+
+#[derive(Args)]
+pub struct HostArgs {
+    /// Address to listen on, `host:port` or `unix:PATH` for a Unix socket
+    #[arg(short, long, default_value = DEFAULT_ADDRESS)]
+    pub address: Address,
+
+    /// Name shown to the other players
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Settings of the races, applied to the configuration
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+#[derive(Args)]
+pub struct JoinArgs {
+    /// Address of the host, `host:port` or `unix:PATH` for a Unix socket
+    #[arg(default_value = DEFAULT_ADDRESS)]
+    pub address: Address,
+
+    /// Name shown to the other players
+    #[arg(long)]
+    pub name: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum CorporaCommand {
+    /// List the embedded and user-supplied corpora
+    List,
+}
+
+#[derive(Args)]
+pub struct RunArgs {
+    /// Corpus name (see `ttl corpora list`), or else path to a corpus file
+    #[arg(short, long)]
+    pub corpus: Option<String>,
+
+    /// Number of words per test, or lines for code corpora
+    #[arg(short = 'n', long)]
+    pub length: Option<usize>,
+
+    /// Run timed tests lasting this many seconds instead of fixed-length ones
+    #[arg(short, long, value_name = "SECONDS")]
+    pub time: Option<u64>,
+
+    /// How words are picked from the corpus
+    #[arg(short, long, value_enum)]
+    pub sampling: Option<SamplingArg>,
+
+    /// Seed for the generator that picks the seed of every test, for a reproducible sequence of
+    /// tests. It is not the seed shown in test codes, which --code takes
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Start with the test shared as this code, as shown on the results screen
+    #[arg(long, conflicts_with_all = ["corpus", "length", "time", "sampling", "seed"])]
+    pub code: Option<TestCode>,
+
+    /// Show frame statistics
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", value_name = "BOOL")]
+    pub frame_statistics: Option<bool>,
+
+    /// Show live typing statistics
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", value_name = "BOOL")]
+    pub live_statistics: Option<bool>,
+
+    /// Compare typed words with the target one at a time, space moving on to the next word
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", value_name = "BOOL")]
+    pub word_by_word: Option<bool>,
+
+    /// How mistakes have to be corrected
+    #[arg(long, value_enum)]
+    pub strictness: Option<StrictnessArg>,
+
+    /// Colour theme name, or else path to a theme file
+    #[arg(long)]
+    pub theme: Option<String>,
+
+    /// Race a ghost of the best past run of the same text
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", value_name = "BOOL")]
+    pub ghost: Option<bool>,
+}
+
+#[derive(Args)]
+pub struct ScriptArgs {
+    /// Script to replay, see the README for its format
+    pub path: std::path::PathBuf,
+
+    /// Write snapshots from the screen instead of comparing with them, creating missing ones
+    #[arg(long)]
+    pub update_snapshots: bool,
+
+    /// Settings the script starts with, applied to the default configuration
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+#[derive(Args)]
+pub struct HistoryArgs {
+    /// Only show tests typed from this corpus
+    #[arg(short, long)]
+    pub corpus: Option<String>,
+
+    /// Order of the listed tests
+    #[arg(long, value_enum, default_value = "date")]
+    pub sort: SortArg,
+
+    /// List the oldest, slowest, least accurate or shortest tests first
+    #[arg(long)]
+    pub ascending: bool,
+
+    /// Maximum number of tests to list
+    #[arg(short = 'n', long)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// File to write, instead of the standard output
+    #[arg(short, long)]
+    pub output: Option<std::path::PathBuf>,
+
+    /// Format of the export, going by the extension of the output file when not given
+    #[arg(short, long, value_enum)]
+    pub format: Option<FormatArg>,
+
+    /// Include every keystroke of the tests, written to FILE.keystrokes.csv for CSV exports
+    #[arg(short, long)]
+    pub keystrokes: bool,
+
+    /// Only export tests typed from this corpus
+    #[arg(short, long)]
+    pub corpus: Option<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum FormatArg {
+    Csv,
+    Json,
+}
+
+impl From<FormatArg> for ExportFormat {
+    fn from(format: FormatArg) -> Self {
+        match format {
+            FormatArg::Csv => ExportFormat::Csv,
+            FormatArg::Json => ExportFormat::Json,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SamplingArg {
+    Top,
+    Random,
+    Adaptive,
+}
+
+impl From<SamplingArg> for SamplingMethod {
+    fn from(sampling: SamplingArg) -> Self {
+        match sampling {
+            SamplingArg::Top => SamplingMethod::Top,
+            SamplingArg::Random => SamplingMethod::Random,
+            SamplingArg::Adaptive => SamplingMethod::Adaptive,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum StrictnessArg {
+    /// Mistakes can be left in the text
+    Lenient,
+    /// Wrong keys are not entered until the right one is pressed
+    StopOnError,
+    /// The test only ends once the text matches exactly
+    MustCorrect,
+}
+
+impl From<StrictnessArg> for Strictness {
+    fn from(strictness: StrictnessArg) -> Self {
+        match strictness {
+            StrictnessArg::Lenient => Strictness::Lenient,
+            StrictnessArg::StopOnError => Strictness::StopOnError,
+            StrictnessArg::MustCorrect => Strictness::MustCorrect,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SortArg {
+    Date,
+    Wpm,
+    Accuracy,
+    Duration,
+}
+
+impl From<SortArg> for HistorySort {
+    fn from(sort: SortArg) -> Self {
+        match sort {
+            SortArg::Date => HistorySort::Date,
+            SortArg::Wpm => HistorySort::Wpm,
+            SortArg::Accuracy => HistorySort::Accuracy,
+            SortArg::Duration => HistorySort::Duration,
+        }
+    }
+}
+
+impl RunArgs {
+    /// Overrides the settings in `config` with the ones given on the command line.
+    pub fn apply(
+        &self,
+        config: &mut Config,
+        corpora: &[Corpus],
+        themes: &[Theme],
+    ) -> std::io::Result<()> {
+        if let Some(corpus) = &self.corpus {
+            config.corpus = Corpus::find(corpora, corpus)?;
+        }
+        let max_test_length = config.corpus.max_test_length();
+        match self.length {
+            Some(length) if length == 0 || length > max_test_length => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "the length must be between 1 and {} for corpus '{}', found {}",
+                        max_test_length, config.corpus.name, length
+                    ),
+                ));
+            }
+            Some(length) => config.test_length = length,
+            // The configured length may not fit a corpus chosen with --corpus.
+            None => {
+                config.test_length = config
+                    .test_length
+                    .clamp(1, std::cmp::max(max_test_length, 1))
+            }
+        }
+        if let Some(time) = self.time {
+            if time == 0 {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "the time limit must be at least one second",
+                ));
+            }
+            config.test_mode = TestMode::Time;
+            config.time_limit = std::time::Duration::from_secs(time);
+        }
+        if let Some(sampling) = self.sampling {
+            config.sampling_method = sampling.into();
+        }
+        if self.seed.is_some() {
+            config.seed = self.seed;
+        }
+        if let Some(frame_statistics) = self.frame_statistics {
+            config.show_frame_statistics = frame_statistics;
+        }
+        if let Some(live_statistics) = self.live_statistics {
+            config.show_live_typing_statistics = live_statistics;
+        }
+        if let Some(word_by_word) = self.word_by_word {
+            config.word_by_word = word_by_word;
+        }
+        if let Some(strictness) = self.strictness {
+            config.strictness = strictness.into();
+        }
+        if let Some(theme) = &self.theme {
+            config.theme = Theme::find(themes, theme)?;
+        }
+        if let Some(ghost) = self.ghost {
+            config.ghost = ghost;
+        }
+        Ok(())
+    }
+}
+
+pub fn print_history(history: &History, args: &HistoryArgs) {
+    let browser = HistoryBrowser {
+        sort: args.sort.into(),
+        descending: !args.ascending,
+        corpus_filter: args.corpus.clone(),
+        ..HistoryBrowser::default()
+    };
+
+    println!(
+        "{:<16}  {:<30}  {:>6}  {:>5}  {:>7}  {:>8}  {:>8}",
+        "Date", "Corpus", "Length", "WPM", "Raw WPM", "Accuracy", "Duration"
+    );
+    for record in browser
+        .rows(history)
+        .into_iter()
+        .take(args.limit.unwrap_or(usize::MAX))
+    {
+        println!(
+            "{:<16}  {:<30}  {:>6}  {:>5.0}  {:>7.0}  {:>7.2}%  {:>7.1}s",
+            format_timestamp(record.timestamp),
+            record.corpus,
+            record.length_label(),
+            record.wpm,
+            record.raw_wpm,
+            record.accuracy * 100.0,
+            record.duration.as_secs_f64()
+        );
+    }
+}
+
+/// Exports the tests of `history` oldest first, to a file or the standard output.
+pub fn export_history(history: &History, args: &ExportArgs) -> std::io::Result<()> {
+    let browser = HistoryBrowser {
+        sort: HistorySort::Date,
+        descending: false,
+        corpus_filter: args.corpus.clone(),
+        ..HistoryBrowser::default()
+    };
+    let export = Export::new(&browser.rows(history), args.keystrokes);
+
+    let format = args
+        .format
+        .map(ExportFormat::from)
+        .or_else(|| args.output.as_deref().and_then(ExportFormat::from_path))
+        .unwrap_or(ExportFormat::Csv);
+
+    let Some(output) = &args.output else {
+        if format == ExportFormat::Csv && args.keystrokes {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "keystrokes exported as CSV go to a file of their own, which needs --output",
+            ));
+        }
+        return export.write(format, std::io::stdout().lock());
+    };
+
+    export.write(
+        format,
+        std::io::BufWriter::new(std::fs::File::create(output)?),
+    )?;
+    if format == ExportFormat::Csv && args.keystrokes {
+        let mut keystrokes_path = output.with_extension("").into_os_string();
+        keystrokes_path.push(".keystrokes.csv");
+        export.write_keystrokes_csv(std::io::BufWriter::new(std::fs::File::create(
+            keystrokes_path,
+        )?))?;
+    }
+    Ok(())
+}
+
+pub fn print_corpora(corpora: &[Corpus]) {
+    println!("{:<30}  {:<5}  {:>7}  Source", "Name", "Kind", "Entries");
+    for corpus in corpora {
+        println!(
+            "{:<30}  {:<5}  {:>7}  {}",
+            corpus.name,
+            match corpus.kind {
+                CorpusKind::Words => "words",
+                CorpusKind::Prose => "prose",
+                CorpusKind::Code => "code",
+            },
+            corpus.words.len(),
+            match &corpus.id {
+                CorpusId::Embedded(_) => "embedded".to_string(),
+                CorpusId::File(path) => path.display().to_string(),
+            }
+        );
+    }
+}
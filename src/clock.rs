@@ -0,0 +1,20 @@
+//! The current time as seen by the model. It is the system time, unless a headless run froze it
+//! to replay a script at its own pace.
+
+use std::{cell::Cell, time::Instant};
+
+thread_local! {
+    static FROZEN: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+pub fn now() -> Instant {
+    FROZEN
+        .with(|frozen| frozen.get())
+        .unwrap_or_else(Instant::now)
+}
+
+/// Stops the clock of the current thread at `time`, or lets it follow the system time again if
+/// `None`.
+pub fn freeze(time: Option<Instant>) {
+    FROZEN.with(|frozen| frozen.set(time));
+}
@@ -0,0 +1,288 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, CorpusId, SamplingMethod},
+    keyboard::KeyboardLayout,
+    keymap::{Keymap, KeymapOverrides},
+    model::{CaretStyle, Config, Strictness, TestMode},
+    theme::Theme,
+};
+
+/// On-disk representation of [`Config`]. Every field is optional in the file, missing ones take
+/// their default value.
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    show_frame_statistics: bool,
+    show_live_typing_statistics: bool,
+    corpus: String,
+    test_length: usize,
+    test_mode: TestMode,
+    /// Duration of timed tests, in seconds.
+    time_limit: u64,
+    sampling: SamplingMethod,
+    keyboard_layout: KeyboardLayout,
+    word_by_word: bool,
+    strictness: Strictness,
+    caret_style: CaretStyle,
+    caret_blink: bool,
+    smooth_caret: bool,
+    ghost: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    export_directory: Option<std::path::PathBuf>,
+    /// Theme name or path to a theme file.
+    theme: String,
+    /// Keys of the commands whose default bindings are replaced, by screen.
+    #[serde(skip_serializing_if = "KeymapOverrides::is_empty")]
+    keys: KeymapOverrides,
+}
+
+impl Default for ConfigFile {
+    fn default() -> Self {
+        ConfigFile::from(&Config::default())
+    }
+}
+
+impl From<&Config> for ConfigFile {
+    fn from(config: &Config) -> Self {
+        ConfigFile {
+            show_frame_statistics: config.show_frame_statistics,
+            show_live_typing_statistics: config.show_live_typing_statistics,
+            corpus: match &config.corpus.id {
+                CorpusId::Embedded(_) => config.corpus.name.clone(),
+                CorpusId::File(path) => path.display().to_string(),
+            },
+            test_length: config.test_length,
+            test_mode: config.test_mode,
+            time_limit: config.time_limit.as_secs(),
+            sampling: config.sampling_method,
+            keyboard_layout: config.keyboard_layout,
+            word_by_word: config.word_by_word,
+            strictness: config.strictness,
+            caret_style: config.caret_style,
+            caret_blink: config.caret_blink,
+            smooth_caret: config.smooth_caret,
+            ghost: config.ghost,
+            export_directory: config.export_directory.clone(),
+            // Themes of the user directory are loaded at every start, so their name is enough.
+            theme: match &config.theme.path {
+                Some(path)
+                    if !Theme::user_directory()
+                        .is_some_and(|directory| path.starts_with(directory)) =>
+                {
+                    path.display().to_string()
+                }
+                _ => config.theme.name.clone(),
+            },
+            keys: config.keymap.overrides.clone(),
+        }
+    }
+}
+
+/// Settings as they are written to the configuration file, to tell which ones changed, see
+/// [`Config::save_changes`].
+pub struct ConfigSnapshot(toml::Table);
+
+impl Config {
+    pub fn default_path() -> Option<std::path::PathBuf> {
+        dirs::config_dir()
+            .map(|config_dir| config_dir.join("terminal_typing_lab").join("config.toml"))
+    }
+
+    /// Reads the configuration file at `path`, resolving the corpus against `corpora` and the
+    /// theme against `themes`. A missing file yields the default configuration, and a corpus or
+    /// theme that cannot be found any more is replaced with the default one, with a warning.
+    pub fn load(
+        path: &std::path::Path,
+        corpora: &[Corpus],
+        themes: &[Theme],
+    ) -> std::io::Result<Config> {
+        let invalid_data = |message: String| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("invalid configuration file {}: {}", path.display(), message),
+            )
+        };
+
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Config::default())
+            }
+            Err(error) => return Err(error),
+        };
+
+        let file: ConfigFile =
+            toml::from_str(&contents).map_err(|error| invalid_data(error.to_string()))?;
+
+        let warn = |error: std::io::Error, fallback: &str| {
+            eprintln!(
+                "warning: {}: {}, using '{}' instead",
+                path.display(),
+                error,
+                fallback
+            );
+        };
+
+        let default = Config::default();
+        let (corpus, test_length) = match Corpus::find(corpora, &file.corpus) {
+            Ok(corpus) => (corpus, file.test_length),
+            Err(error) => {
+                warn(error, &default.corpus.name);
+                let test_length = file
+                    .test_length
+                    .clamp(1, std::cmp::max(default.corpus.max_test_length(), 1));
+                (default.corpus, test_length)
+            }
+        };
+
+        let theme = Theme::find(themes, &file.theme).unwrap_or_else(|error| {
+            warn(error, &default.theme.name);
+            default.theme
+        });
+
+        let keymap = Keymap::new(file.keys).map_err(invalid_data)?;
+
+        if test_length == 0 || test_length > corpus.max_test_length() {
+            return Err(invalid_data(format!(
+                "test_length must be between 1 and {} for corpus '{}', found {}",
+                corpus.max_test_length(),
+                corpus.name,
+                file.test_length
+            )));
+        }
+
+        if file.time_limit == 0 {
+            return Err(invalid_data(
+                "time_limit must be at least one second".to_string(),
+            ));
+        }
+
+        Ok(Config {
+            show_frame_statistics: file.show_frame_statistics,
+            show_live_typing_statistics: file.show_live_typing_statistics,
+            corpus,
+            test_length,
+            test_mode: file.test_mode,
+            time_limit: std::time::Duration::from_secs(file.time_limit),
+            sampling_method: file.sampling,
+            keyboard_layout: file.keyboard_layout,
+            word_by_word: file.word_by_word,
+            strictness: file.strictness,
+            caret_style: file.caret_style,
+            caret_blink: file.caret_blink,
+            smooth_caret: file.smooth_caret,
+            ghost: file.ghost,
+            export_directory: file.export_directory,
+            theme,
+            keymap,
+            ..default
+        })
+    }
+
+    pub fn snapshot(&self) -> ConfigSnapshot {
+        ConfigSnapshot(toml::Table::try_from(ConfigFile::from(self)).unwrap_or_default())
+    }
+
+    /// Writes the settings that changed since `launched`, the configuration the app started
+    /// with, over `saved`, the configuration read from the file. Settings given on the command
+    /// line thus only last for one run, unless they are changed in the app.
+    pub fn save_changes(
+        &self,
+        path: &std::path::Path,
+        saved: &ConfigSnapshot,
+        launched: &ConfigSnapshot,
+    ) -> std::io::Result<()> {
+        let invalid_data =
+            |error: String| std::io::Error::new(std::io::ErrorKind::InvalidData, error);
+
+        let current = self.snapshot();
+        let mut table = saved.0.clone();
+        for key in current.0.keys().chain(launched.0.keys()) {
+            if current.0.get(key) != launched.0.get(key) {
+                match current.0.get(key) {
+                    Some(value) => table.insert(key.clone(), value.clone()),
+                    None => table.remove(key),
+                };
+            }
+        }
+        // Going through `ConfigFile` keeps the settings in their usual order.
+        let file: ConfigFile = table
+            .try_into()
+            .map_err(|error: toml::de::Error| invalid_data(error.to_string()))?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let contents = toml::to_string(&file).map_err(|error| invalid_data(error.to_string()))?;
+        std::fs::write(path, contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_settings_changed_in_the_app_are_saved() {
+        let path = std::env::temp_dir().join(format!("ttl-config-{}.toml", std::process::id()));
+        let (corpora, themes) = (Corpus::all_embedded(), Theme::all_builtin());
+
+        let mut config = Config::default();
+        let saved = config.snapshot();
+        // As given on the command line.
+        config.time_limit = std::time::Duration::from_secs(60);
+        let launched = config.snapshot();
+        // As changed in the app.
+        config.word_by_word = true;
+
+        config.save_changes(&path, &saved, &launched).unwrap();
+        let loaded = Config::load(&path, &corpora, &themes).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(loaded.time_limit, std::time::Duration::from_secs(30));
+        assert!(loaded.word_by_word);
+    }
+
+    #[test]
+    fn missing_corpus_and_theme_fall_back_to_the_default() {
+        let path = std::env::temp_dir().join(format!("ttl-missing-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "corpus = \"/nonexistent/corpus.txt\"\ntheme = \"/nonexistent/theme.toml\"\n",
+        )
+        .unwrap();
+
+        let loaded = Config::load(&path, &Corpus::all_embedded(), &Theme::all_builtin());
+        let _ = std::fs::remove_file(&path);
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.corpus.name, Config::default().corpus.name);
+        assert_eq!(loaded.theme.name, Config::default().theme.name);
+    }
+
+    #[test]
+    fn theme_files_are_saved_by_canonical_path() {
+        let directory = std::env::temp_dir().join(format!("ttl-theme-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("mine.toml"), "base = \"Monochrome\"\n").unwrap();
+        let path = directory
+            .join("..")
+            .join(directory.file_name().unwrap())
+            .join("mine.toml");
+
+        let theme = Theme::find(&Theme::all_builtin(), &path.display().to_string());
+        let canonical_path = directory.join("mine.toml").canonicalize();
+        let _ = std::fs::remove_dir_all(&directory);
+
+        let config = Config {
+            theme: theme.unwrap(),
+            ..Config::default()
+        };
+        assert_eq!(
+            ConfigFile::from(&config).theme,
+            canonical_path.unwrap().display().to_string()
+        );
+    }
+}
@@ -1,142 +1,730 @@
 use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SamplingMethod {
     Top,
     Random,
+    /// Random, but biased towards words that exercise the typist's weak spots.
+    Adaptive,
+}
+
+impl SamplingMethod {
+    pub fn name(self) -> &'static str {
+        match self {
+            SamplingMethod::Top => "Top",
+            SamplingMethod::Random => "Random",
+            SamplingMethod::Adaptive => "Adaptive",
+        }
+    }
 }
 
 #[allow(clippy::enum_variant_names)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum EmbeddedCorpora {
     English200Words,
     English200Bigrams,
     English200Trigrams,
     English200Tetragrams,
+    RustSnippets,
+}
+
+impl EmbeddedCorpora {
+    pub const ALL: [EmbeddedCorpora; 5] = [
+        EmbeddedCorpora::English200Words,
+        EmbeddedCorpora::English200Bigrams,
+        EmbeddedCorpora::English200Trigrams,
+        EmbeddedCorpora::English200Tetragrams,
+        EmbeddedCorpora::RustSnippets,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EmbeddedCorpora::English200Words => "English Top 200 Words",
+            EmbeddedCorpora::English200Bigrams => "English Top 200 Bigrams",
+            EmbeddedCorpora::English200Trigrams => "English Top 200 Trigrams",
+            EmbeddedCorpora::English200Tetragrams => "English Top 200 Tetragrams",
+            EmbeddedCorpora::RustSnippets => "Rust Snippets",
+        }
+    }
+
+    pub fn short_name(self) -> &'static str {
+        match self {
+            EmbeddedCorpora::English200Words => "words",
+            EmbeddedCorpora::English200Bigrams => "bigrams",
+            EmbeddedCorpora::English200Trigrams => "trigrams",
+            EmbeddedCorpora::English200Tetragrams => "tetragrams",
+            EmbeddedCorpora::RustSnippets => "rust",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum CorpusId {
+    Embedded(EmbeddedCorpora),
+    File(std::path::PathBuf),
 }
 
+/// Word corpora are sampled word by word, prose corpora are sampled as contiguous passages so
+/// that the sentences stay readable. The entries of code corpora are whole snippets of source
+/// code, newlines and indentation included, and tests are as many snippets as it takes to reach
+/// the test length in lines.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CorpusKind {
+    Words,
+    Prose,
+    Code,
+}
+
+#[derive(Clone)]
 pub struct Corpus {
-    pub words: Vec<&'static str>,
-    pub id: EmbeddedCorpora,
+    pub name: String,
+    pub language: Option<String>,
+    pub kind: CorpusKind,
+    pub words: Vec<String>,
+    pub id: CorpusId,
+}
+
+#[derive(Deserialize)]
+struct CorpusFile {
+    name: Option<String>,
+    language: Option<String>,
+    words: Vec<String>,
 }
 
 impl Corpus {
-    pub fn sample(&self, n: usize, method: SamplingMethod) -> Corpus {
-        Corpus {
-            words: match method {
-                SamplingMethod::Top => self.words.iter().take(n).cloned().collect(),
-                SamplingMethod::Random => {
-                    let mut rng = rand::thread_rng();
-                    self.words.choose_multiple(&mut rng, n).cloned().collect()
+    /// Picks `n` words from the corpus and joins them with [`Corpus::separator`]. `weight` gives
+    /// the relative likelihood of picking a word and is only used by [`SamplingMethod::Adaptive`].
+    ///
+    /// `position` is where the previous sample of the same text ended, and is moved to where this
+    /// one ends. Samples that follow the order of the corpus, with [`SamplingMethod::Top`] or from
+    /// prose, carry on from there, wrapping around at the end of the corpus, so that timed tests
+    /// read on as they grow instead of starting over.
+    pub fn sample(
+        &self,
+        n: usize,
+        method: SamplingMethod,
+        rng: &mut impl rand::Rng,
+        weight: impl Fn(&str) -> f64,
+        position: &mut Option<usize>,
+    ) -> String {
+        let words = match (method, self.kind) {
+            (_, CorpusKind::Code) => {
+                let start = position.unwrap_or(0);
+                let snippets: Vec<&String> = match method {
+                    SamplingMethod::Top => self.words[start..]
+                        .iter()
+                        .chain(&self.words[..start])
+                        .collect(),
+                    SamplingMethod::Random => {
+                        self.words.choose_multiple(rng, self.words.len()).collect()
+                    }
+                    SamplingMethod::Adaptive => match self.words.choose_multiple_weighted(
+                        rng,
+                        self.words.len(),
+                        |snippet| weight(snippet),
+                    ) {
+                        Ok(snippets) => snippets.collect(),
+                        Err(_) => self.words.choose_multiple(rng, self.words.len()).collect(),
+                    },
+                };
+
+                let mut line_count = 0;
+                let snippets: Vec<String> = snippets
+                    .into_iter()
+                    .take_while(|snippet| {
+                        let is_needed = line_count < n;
+                        line_count += snippet.lines().count();
+                        is_needed
+                    })
+                    .cloned()
+                    .collect();
+                if method == SamplingMethod::Top {
+                    *position = Some((start + snippets.len()) % self.words.len());
+                }
+                snippets
+            }
+            (SamplingMethod::Top, _) => self.read_on(position.unwrap_or(0), n, position),
+            (SamplingMethod::Random, CorpusKind::Words) => {
+                self.words.choose_multiple(rng, n).cloned().collect()
+            }
+            (SamplingMethod::Adaptive, CorpusKind::Words) => {
+                match self
+                    .words
+                    .choose_multiple_weighted(rng, n, |word| weight(word))
+                {
+                    Ok(words) => words.cloned().collect(),
+                    Err(_) => self.words.choose_multiple(rng, n).cloned().collect(),
+                }
+            }
+            (SamplingMethod::Random | SamplingMethod::Adaptive, CorpusKind::Prose) => {
+                let start = position
+                    .unwrap_or_else(|| rng.gen_range(0..=self.words.len().saturating_sub(n)));
+                self.read_on(start, n, position)
+            }
+        };
+        words.join(self.separator())
+    }
+
+    /// The next `n` words in the order of the corpus from `start`, each at most once, moving
+    /// `position` past them.
+    fn read_on(&self, start: usize, n: usize, position: &mut Option<usize>) -> Vec<String> {
+        let n = std::cmp::min(n, self.words.len());
+        *position = Some((start + n) % self.words.len());
+        self.words
+            .iter()
+            .cycle()
+            .skip(start)
+            .take(n)
+            .cloned()
+            .collect()
+    }
+
+    /// Largest possible test length: a number of lines for code corpora, of words otherwise.
+    pub fn max_test_length(&self) -> usize {
+        match self.kind {
+            CorpusKind::Code => self
+                .words
+                .iter()
+                .map(|snippet| snippet.lines().count())
+                .sum(),
+            CorpusKind::Words | CorpusKind::Prose => self.words.len(),
+        }
+    }
+
+    /// Text joining the entries of the corpus, and the texts sampled from it.
+    pub fn separator(&self) -> &'static str {
+        match self.kind {
+            CorpusKind::Code => "\n",
+            CorpusKind::Words | CorpusKind::Prose => " ",
+        }
+    }
+
+    pub fn all_embedded() -> Vec<Corpus> {
+        EmbeddedCorpora::ALL
+            .iter()
+            .map(|&name| Corpus::embedded(name))
+            .collect()
+    }
+
+    /// Finds a corpus by name (ignoring case) among `corpora`, or else by file path, as
+    /// [`Theme::find`](crate::theme::Theme::find) does. Files are loaded from their canonical
+    /// path, which stays valid from any other directory.
+    pub fn find(corpora: &[Corpus], name_or_path: &str) -> std::io::Result<Corpus> {
+        if let Some(corpus) = corpora
+            .iter()
+            .find(|corpus| corpus.name.eq_ignore_ascii_case(name_or_path))
+        {
+            return Ok(corpus.clone());
+        }
+
+        let path = std::path::Path::new(name_or_path);
+        if path.is_file() {
+            return Corpus::load(&path.canonicalize()?);
+        }
+
+        Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!(
+                "unknown corpus '{}', run `ttl corpora list` to see the available ones",
+                name_or_path
+            ),
+        ))
+    }
+
+    /// Name of the corpus inside test codes: short for embedded corpora, and without spaces
+    /// otherwise.
+    pub fn code_name(&self) -> String {
+        match &self.id {
+            CorpusId::Embedded(name) => name.short_name().to_string(),
+            CorpusId::File(_) => self.name.replace(' ', "_"),
+        }
+    }
+
+    /// Directory scanned for user-supplied corpora at startup.
+    pub fn user_directory() -> Option<std::path::PathBuf> {
+        dirs::data_dir().map(|data_dir| data_dir.join("terminal_typing_lab").join("corpora"))
+    }
+
+    /// Loads every corpus file in `directory`, ordered by file name. A missing directory is not
+    /// an error, it simply contains no corpora.
+    pub fn load_directory(directory: &std::path::Path) -> std::io::Result<Vec<Corpus>> {
+        let entries = match std::fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+
+        let mut paths = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let is_hidden = path
+                .file_name()
+                .map_or(true, |name| name.to_string_lossy().starts_with('.'));
+            if path.is_file() && !is_hidden {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        // One unreadable, non-UTF-8 or empty file should not keep the others from loading.
+        Ok(paths
+            .iter()
+            .filter_map(|path| match Corpus::load(path) {
+                Ok(corpus) => Some(corpus),
+                Err(error) => {
+                    eprintln!("warning: skipping corpus {}", error);
+                    None
                 }
-            },
-            id: self.id,
+            })
+            .collect())
+    }
+
+    /// Loads a corpus from a file. JSON files must contain a `words` array and may carry `name`
+    /// and `language` metadata. Source files of a known language are split into code snippets,
+    /// see [`split_snippets`]. Any other file is read as plain text: if every line holds a single
+    /// word it is treated as a word list, otherwise as prose.
+    pub fn load(path: &std::path::Path) -> std::io::Result<Corpus> {
+        let invalid_data = |message: String| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), message),
+            )
+        };
+
+        let contents =
+            std::fs::read_to_string(path).map_err(|error| invalid_data(error.to_string()))?;
+        let file_name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        let corpus = if let Some(language) = code_language(&extension) {
+            Corpus {
+                name: file_name,
+                language: Some(language.to_string()),
+                kind: CorpusKind::Code,
+                words: split_snippets(&contents),
+                id: CorpusId::File(path.to_path_buf()),
+            }
+        } else if extension == "json" {
+            let file: CorpusFile =
+                serde_json::from_str(&contents).map_err(|error| invalid_data(error.to_string()))?;
+            Corpus {
+                name: file.name.unwrap_or(file_name),
+                language: file.language,
+                kind: CorpusKind::Words,
+                words: file
+                    .words
+                    .into_iter()
+                    .map(|word| word.trim().to_string())
+                    .filter(|word| !word.is_empty())
+                    .collect(),
+                id: CorpusId::File(path.to_path_buf()),
+            }
+        } else {
+            let is_word_list = contents
+                .lines()
+                .all(|line| line.split_whitespace().count() <= 1);
+            Corpus {
+                name: file_name,
+                language: None,
+                kind: if is_word_list {
+                    CorpusKind::Words
+                } else {
+                    CorpusKind::Prose
+                },
+                words: contents.split_whitespace().map(str::to_string).collect(),
+                id: CorpusId::File(path.to_path_buf()),
+            }
+        };
+
+        if corpus.words.is_empty() {
+            return Err(invalid_data("corpus contains no words".to_string()));
         }
+
+        Ok(corpus)
     }
 
     pub fn embedded(name: EmbeddedCorpora) -> Corpus {
-        match name {
-            EmbeddedCorpora::English200Words => Corpus {
-                words: vec![
-                    "the", "be", "of", "and", "a", "to", "in", "he", "have", "it", "that", "for",
-                    "they", "I", "with", "as", "not", "on", "she", "at", "by", "this", "we", "you",
-                    "do", "but", "from", "or", "which", "one", "would", "all", "will", "there",
-                    "say", "who", "make", "when", "can", "more", "if", "no", "man", "out", "other",
-                    "so", "what", "time", "up", "go", "about", "than", "into", "could", "state",
-                    "only", "new", "year", "some", "take", "come", "these", "know", "see", "use",
-                    "get", "like", "then", "first", "any", "work", "now", "may", "such", "give",
-                    "over", "think", "most", "even", "find", "day", "also", "after", "way", "many",
-                    "must", "look", "before", "great", "back", "through", "long", "where", "much",
-                    "should", "well", "people", "down", "own", "just", "because", "good", "each",
-                    "those", "feel", "seem", "how", "high", "too", "place", "little", "world",
-                    "very", "still", "nation", "hand", "old", "life", "tell", "write", "become",
-                    "here", "show", "house", "both", "between", "need", "mean", "call", "develop",
-                    "under", "last", "right", "move", "thing", "general", "school", "never",
-                    "same", "another", "begin", "while", "number", "part", "turn", "real", "leave",
-                    "might", "want", "point", "form", "off", "child", "few", "small", "since",
-                    "against", "ask", "late", "home", "interest", "large", "person", "end", "open",
-                    "public", "follow", "during", "present", "without", "again", "hold", "govern",
-                    "around", "possible", "head", "consider", "word", "program", "problem",
-                    "however", "lead", "system", "set", "order", "eye", "plan", "run", "keep",
-                    "face", "fact", "group", "play", "stand", "increase", "early", "course",
-                    "change", "help", "line",
-                ],
-                id: EmbeddedCorpora::English200Words,
-            },
-            EmbeddedCorpora::English200Bigrams => Corpus {
-                words: vec![
-                    "th", "he", "in", "er", "an", "re", "on", "at", "en", "nd", "ti", "es", "or",
-                    "te", "of", "ed", "is", "it", "al", "ar", "st", "to", "nt", "ng", "se", "ha",
-                    "as", "ou", "io", "le", "ve", "co", "me", "de", "hi", "ri", "ro", "ic", "ne",
-                    "ea", "ra", "ce", "li", "ch", "ll", "be", "ma", "si", "om", "ur", "ca", "el",
-                    "ta", "la", "ns", "di", "fo", "ho", "pe", "ec", "pr", "no", "ct", "us", "ac",
-                    "ot", "il", "tr", "ly", "nc", "et", "ut", "ss", "so", "rs", "un", "lo", "wa",
-                    "ge", "ie", "wh", "ee", "wi", "em", "ad", "ol", "rt", "po", "we", "na", "ul",
-                    "ni", "ts", "mo", "ow", "pa", "im", "mi", "ai", "sh", "ir", "su", "id", "os",
-                    "iv", "ia", "am", "fi", "ci", "vi", "pl", "ig", "tu", "ev", "ld", "ry", "mp",
-                    "fe", "bl", "ab", "gh", "ty", "op", "wo", "sa", "ay", "ex", "ke", "fr", "oo",
-                    "av", "ag", "if", "ap", "gr", "od", "bo", "sp", "rd", "do", "uc", "bu", "ei",
-                    "ov", "by", "rm", "ep", "tt", "oc", "fa", "ef", "cu", "rn", "sc", "gi", "da",
-                    "yo", "cr", "cl", "du", "ga", "qu", "ue", "ff", "ba", "ey", "ls", "va", "um",
-                    "pp", "ua", "up", "lu", "go", "ht", "ru", "ug", "ds", "lt", "pi", "rc", "rr",
-                    "eg", "au", "ck", "ew", "mu", "br", "bi", "pt", "ak", "pu", "ui", "rg", "ib",
-                    "tl", "ny", "ki", "rk", "ys",
-                ],
-                id: EmbeddedCorpora::English200Bigrams,
-            },
-            EmbeddedCorpora::English200Trigrams => Corpus {
-                words: vec![
-                    "the", "and", "ing", "ion", "tio", "ent", "ati", "for", "her", "ter", "hat",
-                    "tha", "ere", "ate", "his", "con", "res", "ver", "all", "ons", "nce", "men",
-                    "ith", "ted", "ers", "pro", "thi", "wit", "are", "ess", "not", "ive", "was",
-                    "ect", "rea", "com", "eve", "per", "int", "est", "sta", "cti", "ica", "ist",
-                    "ear", "ain", "one", "our", "iti", "rat", "nte", "tin", "ine", "der", "ome",
-                    "man", "pre", "rom", "tra", "whi", "ave", "str", "act", "ill", "ure", "ide",
-                    "ove", "cal", "ble", "out", "sti", "tic", "oun", "enc", "ore", "ant", "ity",
-                    "fro", "art", "tur", "par", "red", "oth", "eri", "hic", "ies", "ste", "ght",
-                    "ich", "igh", "und", "you", "ort", "era", "wer", "nti", "oul", "nde", "ind",
-                    "tho", "hou", "nal", "but", "hav", "uld", "use", "han", "hin", "een", "ces",
-                    "cou", "lat", "tor", "ese", "age", "ame", "rin", "anc", "ten", "hen", "min",
-                    "eas", "can", "lit", "cha", "ous", "eat", "end", "ssi", "ial", "les", "ren",
-                    "tiv", "nts", "whe", "tat", "abl", "dis", "ran", "wor", "rou", "lin", "had",
-                    "sed", "ont", "ple", "ugh", "inc", "sio", "din", "ral", "ust", "tan", "nat",
-                    "ins", "ass", "pla", "ven", "ell", "she", "ose", "ite", "lly", "rec", "lan",
-                    "ard", "hey", "rie", "pos", "eme", "mor", "den", "oug", "tte", "ned", "rit",
-                    "ime", "sin", "ast", "any", "orm", "ndi", "ona", "spe", "ene", "hei", "ric",
-                    "ice", "ord", "omp", "nes", "sen", "tim", "tri", "ern", "tes", "por", "app",
-                    "lar", "ntr",
-                ],
-                id: EmbeddedCorpora::English200Trigrams,
-            },
-            EmbeddedCorpora::English200Tetragrams => Corpus {
-                words: vec![
-                    "tion", "atio", "that", "ther", "with", "ment", "ions", "this", "here", "from",
-                    "ould", "ting", "hich", "whic", "ctio", "ence", "have", "othe", "ight", "sion",
-                    "ever", "ical", "they", "inte", "ough", "ance", "were", "tive", "over", "ding",
-                    "pres", "nter", "comp", "able", "heir", "thei", "ally", "ated", "ring", "ture",
-                    "cont", "ents", "cons", "rati", "thin", "part", "form", "ning", "ecti", "some",
-                    "port", "enti", "onal", "itio", "hing", "ound", "will", "reat", "comm", "nder",
-                    "time", "emen", "iona", "more", "stat", "stan", "king", "been", "ress", "thou",
-                    "when", "acti", "lati", "them", "spec", "very", "side", "thes", "woul", "tain",
-                    "nati", "rate", "cent", "ount", "rese", "sing", "what", "tate", "even", "work",
-                    "unde", "mber", "hese", "cial", "than", "eral", "ater", "tter", "sent", "fore",
-                    "ract", "ling", "cess", "inst", "erat", "coun", "ange", "ties", "cati", "late",
-                    "mple", "into", "each", "dent", "serv", "only", "abou", "tati", "reas", "ious",
-                    "ssio", "most", "esti", "ness", "ctiv", "lity", "icat", "ster", "ered", "fere",
-                    "ates", "lect", "such", "arti", "indi", "ffer", "stra", "ings", "bout", "rent",
-                    "eren", "atte", "ener", "atur", "roug", "land", "come", "soci", "know", "also",
-                    "llow", "rest", "vers", "chan", "ativ", "ving", "ined", "call", "pers", "essi",
-                    "efor", "like", "gene", "diff", "self", "ause", "prov", "ries", "ilit", "ffic",
-                    "iden", "stor", "evel", "peri", "then", "long", "cond", "fect", "caus", "rica",
-                    "hose", "year", "utio", "esen", "ject", "rodu", "houg", "thro", "oduc", "irst",
-                    "cted", "afte", "tern", "conc", "fter", "firs", "tabl", "char", "once", "enta",
-                ],
-                id: EmbeddedCorpora::English200Tetragrams,
-            },
+        let words: &[&str] = match name {
+            EmbeddedCorpora::RustSnippets => {
+                return Corpus {
+                    name: name.name().to_string(),
+                    language: Some("Rust".to_string()),
+                    kind: CorpusKind::Code,
+                    words: RUST_SNIPPETS
+                        .iter()
+                        .map(|snippet| snippet.trim_matches('\n').to_string())
+                        .collect(),
+                    id: CorpusId::Embedded(name),
+                }
+            }
+            EmbeddedCorpora::English200Words => &[
+                "the", "be", "of", "and", "a", "to", "in", "he", "have", "it", "that", "for",
+                "they", "I", "with", "as", "not", "on", "she", "at", "by", "this", "we", "you",
+                "do", "but", "from", "or", "which", "one", "would", "all", "will", "there", "say",
+                "who", "make", "when", "can", "more", "if", "no", "man", "out", "other", "so",
+                "what", "time", "up", "go", "about", "than", "into", "could", "state", "only",
+                "new", "year", "some", "take", "come", "these", "know", "see", "use", "get",
+                "like", "then", "first", "any", "work", "now", "may", "such", "give", "over",
+                "think", "most", "even", "find", "day", "also", "after", "way", "many", "must",
+                "look", "before", "great", "back", "through", "long", "where", "much", "should",
+                "well", "people", "down", "own", "just", "because", "good", "each", "those",
+                "feel", "seem", "how", "high", "too", "place", "little", "world", "very", "still",
+                "nation", "hand", "old", "life", "tell", "write", "become", "here", "show",
+                "house", "both", "between", "need", "mean", "call", "develop", "under", "last",
+                "right", "move", "thing", "general", "school", "never", "same", "another", "begin",
+                "while", "number", "part", "turn", "real", "leave", "might", "want", "point",
+                "form", "off", "child", "few", "small", "since", "against", "ask", "late", "home",
+                "interest", "large", "person", "end", "open", "public", "follow", "during",
+                "present", "without", "again", "hold", "govern", "around", "possible", "head",
+                "consider", "word", "program", "problem", "however", "lead", "system", "set",
+                "order", "eye", "plan", "run", "keep", "face", "fact", "group", "play", "stand",
+                "increase", "early", "course", "change", "help", "line",
+            ],
+            EmbeddedCorpora::English200Bigrams => &[
+                "th", "he", "in", "er", "an", "re", "on", "at", "en", "nd", "ti", "es", "or", "te",
+                "of", "ed", "is", "it", "al", "ar", "st", "to", "nt", "ng", "se", "ha", "as", "ou",
+                "io", "le", "ve", "co", "me", "de", "hi", "ri", "ro", "ic", "ne", "ea", "ra", "ce",
+                "li", "ch", "ll", "be", "ma", "si", "om", "ur", "ca", "el", "ta", "la", "ns", "di",
+                "fo", "ho", "pe", "ec", "pr", "no", "ct", "us", "ac", "ot", "il", "tr", "ly", "nc",
+                "et", "ut", "ss", "so", "rs", "un", "lo", "wa", "ge", "ie", "wh", "ee", "wi", "em",
+                "ad", "ol", "rt", "po", "we", "na", "ul", "ni", "ts", "mo", "ow", "pa", "im", "mi",
+                "ai", "sh", "ir", "su", "id", "os", "iv", "ia", "am", "fi", "ci", "vi", "pl", "ig",
+                "tu", "ev", "ld", "ry", "mp", "fe", "bl", "ab", "gh", "ty", "op", "wo", "sa", "ay",
+                "ex", "ke", "fr", "oo", "av", "ag", "if", "ap", "gr", "od", "bo", "sp", "rd", "do",
+                "uc", "bu", "ei", "ov", "by", "rm", "ep", "tt", "oc", "fa", "ef", "cu", "rn", "sc",
+                "gi", "da", "yo", "cr", "cl", "du", "ga", "qu", "ue", "ff", "ba", "ey", "ls", "va",
+                "um", "pp", "ua", "up", "lu", "go", "ht", "ru", "ug", "ds", "lt", "pi", "rc", "rr",
+                "eg", "au", "ck", "ew", "mu", "br", "bi", "pt", "ak", "pu", "ui", "rg", "ib", "tl",
+                "ny", "ki", "rk", "ys",
+            ],
+            EmbeddedCorpora::English200Trigrams => &[
+                "the", "and", "ing", "ion", "tio", "ent", "ati", "for", "her", "ter", "hat", "tha",
+                "ere", "ate", "his", "con", "res", "ver", "all", "ons", "nce", "men", "ith", "ted",
+                "ers", "pro", "thi", "wit", "are", "ess", "not", "ive", "was", "ect", "rea", "com",
+                "eve", "per", "int", "est", "sta", "cti", "ica", "ist", "ear", "ain", "one", "our",
+                "iti", "rat", "nte", "tin", "ine", "der", "ome", "man", "pre", "rom", "tra", "whi",
+                "ave", "str", "act", "ill", "ure", "ide", "ove", "cal", "ble", "out", "sti", "tic",
+                "oun", "enc", "ore", "ant", "ity", "fro", "art", "tur", "par", "red", "oth", "eri",
+                "hic", "ies", "ste", "ght", "ich", "igh", "und", "you", "ort", "era", "wer", "nti",
+                "oul", "nde", "ind", "tho", "hou", "nal", "but", "hav", "uld", "use", "han", "hin",
+                "een", "ces", "cou", "lat", "tor", "ese", "age", "ame", "rin", "anc", "ten", "hen",
+                "min", "eas", "can", "lit", "cha", "ous", "eat", "end", "ssi", "ial", "les", "ren",
+                "tiv", "nts", "whe", "tat", "abl", "dis", "ran", "wor", "rou", "lin", "had", "sed",
+                "ont", "ple", "ugh", "inc", "sio", "din", "ral", "ust", "tan", "nat", "ins", "ass",
+                "pla", "ven", "ell", "she", "ose", "ite", "lly", "rec", "lan", "ard", "hey", "rie",
+                "pos", "eme", "mor", "den", "oug", "tte", "ned", "rit", "ime", "sin", "ast", "any",
+                "orm", "ndi", "ona", "spe", "ene", "hei", "ric", "ice", "ord", "omp", "nes", "sen",
+                "tim", "tri", "ern", "tes", "por", "app", "lar", "ntr",
+            ],
+            EmbeddedCorpora::English200Tetragrams => &[
+                "tion", "atio", "that", "ther", "with", "ment", "ions", "this", "here", "from",
+                "ould", "ting", "hich", "whic", "ctio", "ence", "have", "othe", "ight", "sion",
+                "ever", "ical", "they", "inte", "ough", "ance", "were", "tive", "over", "ding",
+                "pres", "nter", "comp", "able", "heir", "thei", "ally", "ated", "ring", "ture",
+                "cont", "ents", "cons", "rati", "thin", "part", "form", "ning", "ecti", "some",
+                "port", "enti", "onal", "itio", "hing", "ound", "will", "reat", "comm", "nder",
+                "time", "emen", "iona", "more", "stat", "stan", "king", "been", "ress", "thou",
+                "when", "acti", "lati", "them", "spec", "very", "side", "thes", "woul", "tain",
+                "nati", "rate", "cent", "ount", "rese", "sing", "what", "tate", "even", "work",
+                "unde", "mber", "hese", "cial", "than", "eral", "ater", "tter", "sent", "fore",
+                "ract", "ling", "cess", "inst", "erat", "coun", "ange", "ties", "cati", "late",
+                "mple", "into", "each", "dent", "serv", "only", "abou", "tati", "reas", "ious",
+                "ssio", "most", "esti", "ness", "ctiv", "lity", "icat", "ster", "ered", "fere",
+                "ates", "lect", "such", "arti", "indi", "ffer", "stra", "ings", "bout", "rent",
+                "eren", "atte", "ener", "atur", "roug", "land", "come", "soci", "know", "also",
+                "llow", "rest", "vers", "chan", "ativ", "ving", "ined", "call", "pers", "essi",
+                "efor", "like", "gene", "diff", "self", "ause", "prov", "ries", "ilit", "ffic",
+                "iden", "stor", "evel", "peri", "then", "long", "cond", "fect", "caus", "rica",
+                "hose", "year", "utio", "esen", "ject", "rodu", "houg", "thro", "oduc", "irst",
+                "cted", "afte", "tern", "conc", "fter", "firs", "tabl", "char", "once", "enta",
+            ],
+        };
+
+        Corpus {
+            name: name.name().to_string(),
+            language: Some("English".to_string()),
+            kind: CorpusKind::Words,
+            words: words.iter().map(|word| word.to_string()).collect(),
+            id: CorpusId::Embedded(name),
         }
     }
 }
 
 impl std::fmt::Display for Corpus {
     fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(formatter, "{}", self.words.join(" "))
+        write!(formatter, "{}", self.words.join(self.separator()))
+    }
+}
+
+/// Language of source files with the given extension, `None` if they are not source code.
+fn code_language(extension: &str) -> Option<&'static str> {
+    match extension {
+        "rs" => Some("Rust"),
+        "c" | "h" => Some("C"),
+        "cc" | "cpp" | "hpp" => Some("C++"),
+        "go" => Some("Go"),
+        "java" => Some("Java"),
+        "js" => Some("JavaScript"),
+        "ts" => Some("TypeScript"),
+        "py" => Some("Python"),
+        _ => None,
+    }
+}
+
+/// Splits a source file into snippets at blank lines followed by an unindented line, so that
+/// every top-level item becomes a snippet of its own, blank lines inside it included. Tabs are
+/// expanded to four spaces and trailing whitespace is dropped, as neither can be seen on screen.
+fn split_snippets(source: &str) -> Vec<String> {
+    let mut snippets = Vec::new();
+    let mut snippet: Vec<String> = Vec::new();
+    let mut previous_line_is_blank = false;
+
+    for line in source.lines() {
+        let line = line.replace('\t', "    ").trim_end().to_string();
+        let starts_item = previous_line_is_blank && !line.is_empty() && !line.starts_with(' ');
+        if starts_item && !snippet.is_empty() {
+            snippets.push(snippet.join("\n").trim_matches('\n').to_string());
+            snippet.clear();
+        }
+        previous_line_is_blank = line.is_empty();
+        snippet.push(line);
+    }
+    snippets.push(snippet.join("\n").trim_matches('\n').to_string());
+
+    snippets.retain(|snippet| !snippet.is_empty());
+    snippets
+}
+
+const RUST_SNIPPETS: [&str; 12] = [
+    r#"
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    println!("{:?}", args);
+}
+"#,
+    r#"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub fn distance(&self, other: &Point) -> f64 {
+        ((self.x - other.x).powi(2) + (self.y - other.y).powi(2)).sqrt()
+    }
+}
+"#,
+    r#"
+pub fn parse_pair<T: std::str::FromStr>(s: &str, separator: char) -> Option<(T, T)> {
+    let index = s.find(separator)?;
+    match (T::from_str(&s[..index]), T::from_str(&s[index + 1..])) {
+        (Ok(l), Ok(r)) => Some((l, r)),
+        _ => None,
+    }
+}
+"#,
+    r#"
+use std::collections::HashMap;
+
+fn word_counts(text: &str) -> HashMap<&str, usize> {
+    let mut counts = HashMap::new();
+    for word in text.split_whitespace() {
+        *counts.entry(word).or_insert(0) += 1;
+    }
+    counts
+}
+"#,
+    r#"
+impl std::fmt::Display for Matrix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in &self.rows {
+            writeln!(f, "{}", row.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(" "))?;
+        }
+        Ok(())
+    }
+}
+"#,
+    r#"
+pub enum Shape {
+    Circle { radius: f64 },
+    Rectangle { width: f64, height: f64 },
+}
+
+pub fn area(shape: &Shape) -> f64 {
+    match *shape {
+        Shape::Circle { radius } => std::f64::consts::PI * radius * radius,
+        Shape::Rectangle { width, height } => width * height,
+    }
+}
+"#,
+    r#"
+fn read_config(path: &std::path::Path) -> Result<String, Box<dyn std::error::Error>> {
+    let contents = std::fs::read_to_string(path)?;
+    if contents.trim().is_empty() {
+        return Err("empty configuration".into());
+    }
+    Ok(contents)
+}
+"#,
+    r#"
+pub trait Animal {
+    fn name(&self) -> &str;
+
+    fn speak(&self) -> String {
+        format!("{} makes a sound", self.name())
+    }
+}
+"#,
+    r#"
+let handles: Vec<_> = (0..4)
+    .map(|i| {
+        std::thread::spawn(move || {
+            let sum: u64 = (0..1_000 * i).sum();
+            sum
+        })
+    })
+    .collect();
+"#,
+    r#"
+fn largest<T: PartialOrd + Copy>(list: &[T]) -> Option<T> {
+    let mut iter = list.iter();
+    let mut largest = *iter.next()?;
+    for &item in iter {
+        if item > largest {
+            largest = item;
+        }
+    }
+    Some(largest)
+}
+"#,
+    r#"
+struct Counter {
+    count: u32,
+}
+
+impl Iterator for Counter {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.count < 5 {
+            self.count += 1;
+            Some(self.count)
+        } else {
+            None
+        }
+    }
+}
+"#,
+    r#"
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_adds_two() {
+        assert_eq!(add_two(2), 4);
+    }
+}
+"#,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn corpus(kind: CorpusKind, words: &str) -> Corpus {
+        Corpus {
+            name: "Test".to_string(),
+            language: None,
+            kind,
+            words: words.split_whitespace().map(str::to_string).collect(),
+            id: CorpusId::File("test.txt".into()),
+        }
+    }
+
+    /// Samples `lengths` one after the other, as a growing timed test does.
+    fn samples(corpus: &Corpus, method: SamplingMethod, lengths: &[usize]) -> Vec<String> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let mut position = None;
+        lengths
+            .iter()
+            .map(|&n| corpus.sample(n, method, &mut rng, |_| 1.0, &mut position))
+            .collect()
+    }
+
+    #[test]
+    fn top_samples_read_on() {
+        let words = corpus(CorpusKind::Words, "a b c d e");
+        assert_eq!(
+            samples(&words, SamplingMethod::Top, &[2, 2, 2, 9]),
+            ["a b", "c d", "e a", "b c d e a"]
+        );
+    }
+
+    #[test]
+    fn prose_samples_read_on() {
+        let prose = corpus(CorpusKind::Prose, "one two three four five six seven eight");
+        let text = samples(&prose, SamplingMethod::Random, &[3, 3, 3]).join(" ");
+        let start = prose
+            .words
+            .iter()
+            .position(|word| text.starts_with(word.as_str()));
+        let expected: Vec<&String> = prose
+            .words
+            .iter()
+            .cycle()
+            .skip(start.unwrap())
+            .take(9)
+            .collect();
+        assert_eq!(text.split(' ').collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn files_that_are_not_corpora_are_skipped() {
+        let directory = std::env::temp_dir().join(format!("ttl-corpora-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("empty.txt"), "").unwrap();
+        std::fs::write(directory.join("binary.txt"), b"\xff\xfe").unwrap();
+        std::fs::write(directory.join("words.txt"), "alpha\nbeta\n").unwrap();
+
+        let corpora = Corpus::load_directory(&directory);
+        let _ = std::fs::remove_dir_all(&directory);
+
+        let names: Vec<String> = corpora
+            .unwrap()
+            .into_iter()
+            .map(|corpus| corpus.name)
+            .collect();
+        assert_eq!(names, ["words"]);
+    }
+
+    #[test]
+    fn names_are_found_before_paths() {
+        let directory = std::env::temp_dir().join(format!("ttl-find-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("words.txt");
+        std::fs::write(&path, "alpha\nbeta\n").unwrap();
+
+        let named = Corpus {
+            name: path.display().to_string(),
+            ..corpus(CorpusKind::Words, "a b")
+        };
+        let by_name = Corpus::find(&[named], &path.display().to_string());
+        let by_path = Corpus::find(&[], &path.display().to_string());
+        let _ = std::fs::remove_dir_all(&directory);
+
+        assert_eq!(by_name.unwrap().words, ["a", "b"]);
+        assert_eq!(by_path.unwrap().words, ["alpha", "beta"]);
     }
 }
@@ -0,0 +1,503 @@
+//! Writes test results, and optionally the keystrokes of every test, as CSV or JSON for charting
+//! progress in other tools. Both formats are described in the README, and carry
+//! [`SCHEMA_VERSION`] so that scripts reading them can tell when their layout changes.
+
+use serde::Serialize;
+
+use crate::{
+    alignment::ErrorCounts,
+    history::{format_rfc3339, History, TestRecord},
+    model::{KeystrokeKind, Screen, Strictness, TestMode},
+    replay::Replay,
+};
+
+/// Version of the exported fields, bumped whenever one is renamed, removed or changes meaning.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    /// Format of a file, going by its extension.
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(ExportFormat::Csv),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Export {
+    pub schema_version: u32,
+    pub tests: Vec<ExportedTest>,
+}
+
+#[derive(Serialize)]
+pub struct ExportedTest {
+    /// When the test was finished.
+    pub timestamp: String,
+    pub corpus: String,
+    pub mode: TestMode,
+    /// Number of words, for tests in the words mode.
+    pub test_length: Option<usize>,
+    pub time_limit_seconds: Option<u64>,
+    pub word_by_word: bool,
+    pub strictness: Strictness,
+    pub seed: Option<u64>,
+    pub wpm: f64,
+    pub raw_wpm: f64,
+    pub accuracy: f64,
+    pub completion: f64,
+    pub duration_seconds: f64,
+    pub errors: ErrorCounts,
+    pub target_text: String,
+    /// Only exported on request, and empty for tests recorded without their keystrokes. The
+    /// history only keeps the inputs of a test, so these are derived by replaying them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keystrokes: Option<Vec<ExportedKeystroke>>,
+}
+
+#[derive(Serialize)]
+pub struct ExportedKeystroke {
+    /// Time since the start of the test.
+    pub time_ms: u64,
+    pub action: KeystrokeAction,
+    /// For inputs, index of the grapheme of the target text the input was compared with. For
+    /// deletions, index of the first deleted grapheme of the typed text.
+    pub position: usize,
+    /// Grapheme the input produced, for inputs. A combining mark gives the grapheme it changed.
+    pub typed: Option<String>,
+    /// Grapheme of the target text the input was compared with, for inputs within the text.
+    pub expected: Option<String>,
+    /// Whether the input matched the target text, for inputs.
+    pub is_correct: Option<bool>,
+    /// Whether the input retyped a deleted grapheme, for inputs.
+    pub is_correction: Option<bool>,
+    /// Number of deleted graphemes, for deletions.
+    pub deleted: Option<usize>,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeystrokeAction {
+    Input,
+    DeleteCharacter,
+    DeleteWord,
+}
+
+impl KeystrokeAction {
+    fn name(self) -> &'static str {
+        match self {
+            KeystrokeAction::Input => "input",
+            KeystrokeAction::DeleteCharacter => "delete-character",
+            KeystrokeAction::DeleteWord => "delete-word",
+        }
+    }
+}
+
+const TEST_COLUMNS: [&str; 20] = [
+    "schema_version",
+    "test",
+    "timestamp",
+    "corpus",
+    "mode",
+    "test_length",
+    "time_limit_seconds",
+    "word_by_word",
+    "strictness",
+    "seed",
+    "wpm",
+    "raw_wpm",
+    "accuracy",
+    "completion",
+    "duration_seconds",
+    "substitutions",
+    "insertions",
+    "omissions",
+    "transpositions",
+    "target_text",
+];
+
+const KEYSTROKE_COLUMNS: [&str; 10] = [
+    "schema_version",
+    "test",
+    "time_ms",
+    "action",
+    "position",
+    "typed",
+    "expected",
+    "is_correct",
+    "is_correction",
+    "deleted",
+];
+
+impl Export {
+    /// Exports `records` in order, with their keystrokes if `keystrokes` is set.
+    pub fn new(records: &[&TestRecord], keystrokes: bool) -> Self {
+        Export {
+            schema_version: SCHEMA_VERSION,
+            tests: records
+                .iter()
+                .map(|record| ExportedTest::new(record, keystrokes))
+                .collect(),
+        }
+    }
+
+    pub fn write(&self, format: ExportFormat, writer: impl std::io::Write) -> std::io::Result<()> {
+        match format {
+            ExportFormat::Csv => self.write_tests_csv(writer),
+            ExportFormat::Json => self.write_json(writer),
+        }
+    }
+
+    pub fn write_json(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)
+    }
+
+    /// Writes one row per test, numbered from 1 in the `test` column.
+    pub fn write_tests_csv(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
+        write_csv_row(&mut writer, TEST_COLUMNS)?;
+        for (index, test) in self.tests.iter().enumerate() {
+            write_csv_row(
+                &mut writer,
+                [
+                    SCHEMA_VERSION.to_string(),
+                    (index + 1).to_string(),
+                    test.timestamp.clone(),
+                    test.corpus.clone(),
+                    match test.mode {
+                        TestMode::Words => "words",
+                        TestMode::Time => "time",
+                    }
+                    .to_string(),
+                    optional(test.test_length),
+                    optional(test.time_limit_seconds),
+                    test.word_by_word.to_string(),
+                    match test.strictness {
+                        Strictness::Lenient => "lenient",
+                        Strictness::StopOnError => "stop-on-error",
+                        Strictness::MustCorrect => "must-correct",
+                    }
+                    .to_string(),
+                    optional(test.seed),
+                    test.wpm.to_string(),
+                    test.raw_wpm.to_string(),
+                    test.accuracy.to_string(),
+                    test.completion.to_string(),
+                    test.duration_seconds.to_string(),
+                    test.errors.substitutions.to_string(),
+                    test.errors.insertions.to_string(),
+                    test.errors.omissions.to_string(),
+                    test.errors.transpositions.to_string(),
+                    test.target_text.clone(),
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes one row per keystroke, the `test` column referring to the rows written by
+    /// [`Export::write_tests_csv`].
+    pub fn write_keystrokes_csv(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
+        write_csv_row(&mut writer, KEYSTROKE_COLUMNS)?;
+        for (index, test) in self.tests.iter().enumerate() {
+            for keystroke in test.keystrokes.iter().flatten() {
+                write_csv_row(
+                    &mut writer,
+                    [
+                        SCHEMA_VERSION.to_string(),
+                        (index + 1).to_string(),
+                        keystroke.time_ms.to_string(),
+                        keystroke.action.name().to_string(),
+                        keystroke.position.to_string(),
+                        keystroke.typed.clone().unwrap_or_default(),
+                        keystroke.expected.clone().unwrap_or_default(),
+                        optional(keystroke.is_correct),
+                        optional(keystroke.is_correction),
+                        optional(keystroke.deleted),
+                    ],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes every format, keystrokes included, to files named after `stem`: `STEM.json`,
+    /// `STEM.csv` and `STEM.keystrokes.csv`.
+    pub fn write_files(&self, stem: &std::path::Path) -> std::io::Result<()> {
+        if let Some(parent) = stem.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let create = |suffix: &str| {
+            let mut path = stem.as_os_str().to_owned();
+            path.push(suffix);
+            std::fs::File::create(path).map(std::io::BufWriter::new)
+        };
+        self.write_json(create(".json")?)?;
+        self.write_tests_csv(create(".csv")?)?;
+        self.write_keystrokes_csv(create(".keystrokes.csv")?)
+    }
+}
+
+impl ExportedTest {
+    fn new(record: &TestRecord, keystrokes: bool) -> Self {
+        ExportedTest {
+            timestamp: format_rfc3339(record.timestamp),
+            corpus: record.corpus.clone(),
+            mode: match record.time_limit {
+                Some(_) => TestMode::Time,
+                None => TestMode::Words,
+            },
+            test_length: match record.time_limit {
+                Some(_) => None,
+                None => Some(record.test_length),
+            },
+            time_limit_seconds: record.time_limit.map(|time_limit| time_limit.as_secs()),
+            word_by_word: record.word_by_word,
+            strictness: record.strictness,
+            seed: record.seed,
+            wpm: record.wpm,
+            raw_wpm: record.raw_wpm,
+            accuracy: record.accuracy,
+            completion: record.completion,
+            duration_seconds: record.duration.as_secs_f64(),
+            errors: record.errors,
+            target_text: record.target_text.clone(),
+            keystrokes: keystrokes.then(|| exported_keystrokes(record)),
+        }
+    }
+}
+
+/// Keystrokes of a recorded test, found by replaying its inputs. Replays are deterministic, so
+/// these are the keystrokes the test had when it was typed.
+fn exported_keystrokes(record: &TestRecord) -> Vec<ExportedKeystroke> {
+    let now = std::time::Instant::now();
+    let Some(mut replay) = Replay::new(record, Screen::History, now) else {
+        return Vec::new();
+    };
+    replay.seek(record.duration, now);
+
+    replay
+        .test
+        .keystrokes
+        .iter()
+        .map(|keystroke| {
+            let time_ms = keystroke.time.as_millis() as u64;
+            match &keystroke.kind {
+                KeystrokeKind::Input {
+                    position,
+                    typed,
+                    expected,
+                    is_correction,
+                } => ExportedKeystroke {
+                    time_ms,
+                    action: KeystrokeAction::Input,
+                    position: *position,
+                    typed: Some(typed.clone()),
+                    expected: expected.clone(),
+                    is_correct: Some(expected.as_ref() == Some(typed)),
+                    is_correction: Some(*is_correction),
+                    deleted: None,
+                },
+                KeystrokeKind::DeleteCharacter { span } | KeystrokeKind::DeleteWord { span } => {
+                    ExportedKeystroke {
+                        time_ms,
+                        action: match keystroke.kind {
+                            KeystrokeKind::DeleteWord { .. } => KeystrokeAction::DeleteWord,
+                            _ => KeystrokeAction::DeleteCharacter,
+                        },
+                        position: span.start,
+                        typed: None,
+                        expected: None,
+                        is_correct: None,
+                        is_correction: None,
+                        deleted: Some(span.len()),
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+/// Where exports made from within `ttl` go when the configuration does not say otherwise: next
+/// to the history file. There is none for a history kept in memory, as in scripts, which must
+/// not write to the user's data.
+pub fn default_directory(history: &History) -> Option<std::path::PathBuf> {
+    let directory = history.path.as_deref()?.parent()?;
+    Some(directory.join("exports"))
+}
+
+/// Name of an export made at `timestamp`, without extension, safe on every file system.
+pub fn file_stem(timestamp: std::time::SystemTime) -> String {
+    format!("ttl-{}", format_rfc3339(timestamp).replace(':', "-"))
+}
+
+/// Path without extension of a new export to `directory` made at `timestamp`, numbered from 2
+/// when earlier exports made in the same second would be overwritten.
+pub fn unused_stem(
+    directory: &std::path::Path,
+    timestamp: std::time::SystemTime,
+) -> std::path::PathBuf {
+    let stem = file_stem(timestamp);
+    (1..)
+        .map(|number| match number {
+            1 => directory.join(&stem),
+            _ => directory.join(format!("{}-{}", stem, number)),
+        })
+        .find(|path| {
+            [".json", ".csv", ".keystrokes.csv"].iter().all(|suffix| {
+                let mut path = path.as_os_str().to_owned();
+                path.push(suffix);
+                !std::path::Path::new(&path).exists()
+            })
+        })
+        .unwrap()
+}
+
+fn optional(value: Option<impl ToString>) -> String {
+    value.map_or(String::new(), |value| value.to_string())
+}
+
+/// Writes a CSV row, quoting the fields that need it as described in RFC 4180.
+fn write_csv_row(
+    writer: &mut impl std::io::Write,
+    fields: impl IntoIterator<Item = impl AsRef<str>>,
+) -> std::io::Result<()> {
+    let fields: Vec<String> = fields
+        .into_iter()
+        .map(|field| {
+            let field = field.as_ref();
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+    write!(writer, "{}\r\n", fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Test;
+
+    fn record() -> TestRecord {
+        let start = std::time::Instant::now();
+        let mut test = Test::new("a, \"b\"");
+        let at = |milliseconds| start + std::time::Duration::from_millis(milliseconds);
+        test.input('a', at(0));
+        test.input('x', at(100));
+        test.delete_character(at(200));
+        for (index, c) in ", \"b\"".chars().enumerate() {
+            test.input(c, at(300 + 100 * index as u64));
+        }
+        assert!(test.is_finished());
+        TestRecord::new(&test, "Corpus", 2)
+    }
+
+    #[test]
+    fn csv_quotes_fields() {
+        let record = record();
+        let export = Export::new(&[&record], false);
+        let mut csv = Vec::new();
+        export.write_tests_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(lines[0], TEST_COLUMNS.join(","));
+        assert!(lines[1].starts_with("1,1,"));
+        assert!(lines[1].ends_with(",\"a, \"\"b\"\"\""));
+        assert_eq!(lines[2], "");
+    }
+
+    #[test]
+    fn exports_in_the_same_second_are_numbered() {
+        let directory = std::env::temp_dir().join(format!("ttl-exports-{}", std::process::id()));
+        let timestamp = std::time::SystemTime::UNIX_EPOCH;
+        let record = record();
+        let export = Export::new(&[&record], true);
+
+        let first = unused_stem(&directory, timestamp);
+        export.write_files(&first).unwrap();
+        let second = unused_stem(&directory, timestamp);
+        let _ = std::fs::remove_dir_all(&directory);
+
+        assert_eq!(first, directory.join("ttl-1970-01-01T00-00-00Z"));
+        assert_eq!(second, directory.join("ttl-1970-01-01T00-00-00Z-2"));
+    }
+
+    #[test]
+    fn keystrokes_record_the_typed_grapheme() {
+        let start = std::time::Instant::now();
+        let mut test = Test::new("\u{e9}t\u{e9}");
+        for c in "e\u{301}t\u{e9}".chars() {
+            test.input(c, start);
+        }
+        let record = TestRecord::new(&test, "Corpus", 1);
+
+        let export = Export::new(&[&record], true);
+        let keystrokes = export.tests[0].keystrokes.as_ref().unwrap();
+        let typed: Vec<_> = keystrokes
+            .iter()
+            .map(|keystroke| keystroke.typed.as_deref())
+            .collect();
+        assert_eq!(
+            typed,
+            [Some("e"), Some("\u{e9}"), Some("t"), Some("\u{e9}")]
+        );
+        assert_eq!(keystrokes[1].is_correct, Some(true));
+    }
+
+    #[test]
+    fn keystrokes_are_replayed() {
+        let record = record();
+        let export = Export::new(&[&record], true);
+        let keystrokes = export.tests[0].keystrokes.as_ref().unwrap();
+        assert_eq!(keystrokes.len(), 8);
+
+        let mistake = &keystrokes[1];
+        assert_eq!(mistake.time_ms, 100);
+        assert_eq!(mistake.typed.as_deref(), Some("x"));
+        assert_eq!(mistake.expected.as_deref(), Some(","));
+        assert_eq!(mistake.is_correct, Some(false));
+
+        let deletion = &keystrokes[2];
+        assert_eq!(deletion.action.name(), "delete-character");
+        assert_eq!((deletion.position, deletion.deleted), (1, Some(1)));
+
+        let correction = &keystrokes[3];
+        assert_eq!(correction.is_correct, Some(true));
+        assert_eq!(correction.is_correction, Some(true));
+
+        let mut csv = Vec::new();
+        export.write_keystrokes_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.split("\r\n").count(), 1 + 8 + 1);
+        assert!(csv.contains("\r\n1,1,200,delete-character,1,,,,,1\r\n"));
+    }
+
+    #[test]
+    fn json_is_versioned() {
+        let record = record();
+        let mut json = Vec::new();
+        Export::new(&[&record], false)
+            .write_json(&mut json)
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+
+        assert_eq!(json["schema_version"], SCHEMA_VERSION);
+        let test = &json["tests"][0];
+        assert_eq!(test["mode"], "words");
+        assert_eq!(test["test_length"], 2);
+        assert_eq!(test["completion"], 1.0);
+        assert_eq!(test["errors"]["substitutions"], 0);
+        assert!(test.get("keystrokes").is_none());
+    }
+}
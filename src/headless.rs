@@ -0,0 +1,475 @@
+//! Drives a [`Model`] from a script instead of a terminal, rendering to ratatui's [`TestBackend`]
+//! on a frozen [`clock`], so that whole sessions can be replayed and their screens checked.
+//!
+//! A script holds one step per line, blank lines and lines starting with `#` are skipped:
+//! - `size 100x30` resizes the screen, 100 by 30 being the initial size,
+//! - `pace 120ms` sets the time between keystrokes, 100ms by default,
+//! - `wait 2s` lets time pass,
+//! - `type some text` types the rest of the line, `\n` standing for a newline and `\\` for a
+//!   backslash,
+//! - `type-target 10` types the next 10 graphemes of the target text without mistakes, or the
+//!   whole rest of the test without a count,
+//! - `key Control-r 2` presses a key, twice here, through the keymap of the current screen,
+//! - `expect some text` fails unless the screen shows the rest of the line,
+//! - `snapshot name` compares the screen with `<script>.<name>.snap` next to the script, and
+//!   writes that file if it does not exist yet,
+//! - `print` writes the screen to standard output.
+
+use ratatui::{backend::TestBackend, Terminal};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    action::{Action, TimedAction},
+    cli::RunArgs,
+    clock,
+    corpus::Corpus,
+    history::History,
+    keymap::KeyBinding,
+    model::{Config, Model},
+    theme::Theme,
+};
+
+enum Step {
+    Size(u16, u16),
+    Pace(std::time::Duration),
+    Wait(std::time::Duration),
+    Type(String),
+    TypeTarget(Option<usize>),
+    Key(KeyBinding, usize),
+    Expect(String),
+    Snapshot(String),
+    Print,
+}
+
+pub struct Script {
+    path: std::path::PathBuf,
+    /// Steps with their line number.
+    steps: Vec<(usize, Step)>,
+}
+
+fn parse_duration(text: &str) -> Option<std::time::Duration> {
+    if let Some(milliseconds) = text.strip_suffix("ms") {
+        milliseconds
+            .parse()
+            .ok()
+            .map(std::time::Duration::from_millis)
+    } else {
+        text.strip_suffix('s')?
+            .parse()
+            .ok()
+            .map(std::time::Duration::from_secs_f64)
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut characters = text.chars();
+    while let Some(character) = characters.next() {
+        match (character, characters.clone().next()) {
+            ('\\', Some('n')) => {
+                unescaped.push('\n');
+                characters.next();
+            }
+            ('\\', Some('\\')) => {
+                unescaped.push('\\');
+                characters.next();
+            }
+            _ => unescaped.push(character),
+        }
+    }
+    unescaped
+}
+
+impl Script {
+    pub fn load(path: &std::path::Path) -> std::io::Result<Script> {
+        Script::parse(path, &std::fs::read_to_string(path)?)
+    }
+
+    /// Parses `source`, the contents of the script at `path`, which snapshots are stored next to.
+    pub fn parse(path: &std::path::Path, source: &str) -> std::io::Result<Script> {
+        let mut steps = Vec::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            let (command, argument) = trimmed.split_once(' ').unwrap_or((trimmed, ""));
+            let invalid = |message: String| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("{}:{}: {}", path.display(), line_number, message),
+                )
+            };
+            let duration = || {
+                parse_duration(argument.trim())
+                    .ok_or_else(|| invalid(format!("invalid duration '{}'", argument)))
+            };
+
+            let step = match command {
+                "size" => argument
+                    .trim()
+                    .split_once('x')
+                    .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+                    .filter(|&(width, height)| width > 0 && height > 0)
+                    .map(|(width, height)| Step::Size(width, height))
+                    .ok_or_else(|| invalid(format!("invalid size '{}'", argument)))?,
+                "pace" => Step::Pace(duration()?),
+                "wait" => Step::Wait(duration()?),
+                // Only the separating space is dropped, the text may start with spaces.
+                "type" => Step::Type(unescape(
+                    line.trim_start()
+                        .strip_prefix("type ")
+                        .unwrap_or_default()
+                        .trim_end_matches(['\r', '\n']),
+                )),
+                "type-target" => Step::TypeTarget(match argument.trim() {
+                    "" => None,
+                    count => Some(
+                        count
+                            .parse()
+                            .map_err(|_| invalid(format!("invalid count '{}'", count)))?,
+                    ),
+                }),
+                "key" => {
+                    let mut words = argument.split_whitespace();
+                    let key = words
+                        .next()
+                        .ok_or_else(|| invalid("missing key".to_string()))?
+                        .parse()
+                        .map_err(invalid)?;
+                    let count = match words.next() {
+                        Some(count) => count
+                            .parse()
+                            .map_err(|_| invalid(format!("invalid count '{}'", count)))?,
+                        None => 1,
+                    };
+                    Step::Key(key, count)
+                }
+                "expect" => Step::Expect(argument.to_string()),
+                "snapshot" if !argument.trim().is_empty() => {
+                    Step::Snapshot(argument.trim().to_string())
+                }
+                "snapshot" => return Err(invalid("missing snapshot name".to_string())),
+                "print" => Step::Print,
+                _ => return Err(invalid(format!("unknown step '{}'", command))),
+            };
+            steps.push((line_number, step));
+        }
+
+        Ok(Script {
+            path: path.to_path_buf(),
+            steps,
+        })
+    }
+
+    fn snapshot_path(&self, name: &str) -> std::path::PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.path.with_file_name(format!("{}.{}.snap", stem, name))
+    }
+}
+
+/// The model a script starts from: embedded corpora, built-in themes, an empty history that is
+/// never saved and nowhere to export tests to, and the default configuration with `args`
+/// applied. Words are picked with seed 0 unless `args` gives another one.
+pub fn model(args: &RunArgs) -> std::io::Result<Model> {
+    let corpora = Corpus::all_embedded();
+    let themes = Theme::all_builtin();
+    let mut config = Config {
+        show_frame_statistics: false,
+        seed: Some(0),
+        ..Config::default()
+    };
+    args.apply(&mut config, &corpora, &themes)?;
+
+    let mut model = Model::new(config, corpora, themes, History::in_memory());
+    if let Some(code) = &args.code {
+        model.start_test_from_code(code)?;
+    }
+    Ok(model)
+}
+
+/// Runs scripts against a model, rendering it like the main loop does after every step.
+pub struct HeadlessRunner {
+    pub model: Model,
+    terminal: Terminal<TestBackend>,
+    /// Write snapshots from the screen instead of comparing with them.
+    pub update_snapshots: bool,
+    pace: std::time::Duration,
+    now: std::time::Instant,
+}
+
+impl HeadlessRunner {
+    /// Takes over the clock of the current thread until the runner is dropped.
+    pub fn new(model: Model) -> std::io::Result<HeadlessRunner> {
+        let now = std::time::Instant::now();
+        clock::freeze(Some(now));
+
+        let mut runner = HeadlessRunner {
+            model,
+            terminal: Terminal::new(TestBackend::new(100, 30))?,
+            update_snapshots: false,
+            pace: std::time::Duration::from_millis(100),
+            now,
+        };
+        runner.render()?;
+        Ok(runner)
+    }
+
+    pub fn run(&mut self, script: &Script) -> std::io::Result<()> {
+        for (line_number, step) in &script.steps {
+            if self.model.should_quit {
+                break;
+            }
+
+            let failure = |message: String| {
+                std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("{}:{}: {}", script.path.display(), line_number, message),
+                )
+            };
+
+            match step {
+                Step::Size(width, height) => {
+                    self.terminal.backend_mut().resize(*width, *height);
+                    self.update(Action::Resize)?;
+                }
+                Step::Pace(pace) => self.pace = *pace,
+                Step::Wait(duration) => self.advance(*duration)?,
+                Step::Type(text) => {
+                    for character in text.chars() {
+                        self.keystroke(Action::CharacterInput(character))?;
+                    }
+                }
+                Step::TypeTarget(count) => {
+                    for _ in 0..count.unwrap_or(usize::MAX) {
+                        let test = &self.model.current_test;
+                        if test.is_finished() {
+                            break;
+                        }
+                        let Some(grapheme) =
+                            test.target_text.graphemes(true).nth(test.target_position())
+                        else {
+                            break;
+                        };
+                        for character in grapheme.to_string().chars() {
+                            self.keystroke(Action::CharacterInput(character))?;
+                        }
+                    }
+                }
+                Step::Key(key, count) => {
+                    for _ in 0..*count {
+                        let action = self.model.config.keymap.action(
+                            self.model.screen,
+                            *key,
+                            self.model.current_test.accepts_newlines(),
+                        );
+                        if let Some(action) = action {
+                            self.keystroke(action)?;
+                        }
+                    }
+                }
+                Step::Expect(text) => {
+                    let screen = self.screen();
+                    if !screen.contains(text.as_str()) {
+                        return Err(failure(format!(
+                            "expected '{}' on the screen:\n{}",
+                            text, screen
+                        )));
+                    }
+                }
+                Step::Snapshot(name) => {
+                    let path = script.snapshot_path(name);
+                    let screen = self.screen();
+                    if self.update_snapshots {
+                        std::fs::write(&path, screen)?;
+                        continue;
+                    }
+                    // Snapshots may have been checked out with Windows line endings.
+                    match std::fs::read_to_string(&path)
+                        .map(|expected| expected.replace("\r\n", "\n"))
+                    {
+                        Ok(expected) if expected == screen => {}
+                        Ok(expected) => {
+                            return Err(failure(format!(
+                                "the screen does not match {}, expected:\n{}\nfound:\n{}",
+                                path.display(),
+                                expected,
+                                screen
+                            )));
+                        }
+                        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                            return Err(failure(format!(
+                                "{} does not exist, run with --update-snapshots to create it",
+                                path.display()
+                            )));
+                        }
+                        Err(error) => return Err(error),
+                    }
+                }
+                Step::Print => println!("{}", self.screen()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Text of the rendered screen, one line per row without trailing spaces.
+    pub fn screen(&self) -> String {
+        let buffer = self.terminal.backend().buffer();
+        let mut screen = String::new();
+        for y in 0..buffer.area.height {
+            let row: String = (0..buffer.area.width)
+                .map(|x| buffer.get(x, y).symbol())
+                .collect();
+            screen.push_str(row.trim_end());
+            screen.push('\n');
+        }
+        screen
+    }
+
+    fn keystroke(&mut self, action: Action) -> std::io::Result<()> {
+        self.advance(self.pace)?;
+        self.update(action)
+    }
+
+    fn update(&mut self, action: Action) -> std::io::Result<()> {
+        self.model.update(TimedAction {
+            action,
+            time: self.now,
+        });
+        self.render()
+    }
+
+    /// Lets `duration` pass, going through every timer of the model on the way.
+    fn advance(&mut self, duration: std::time::Duration) -> std::io::Result<()> {
+        let end = self.now + duration;
+        while let Some(wait) = self.model.time_until_next_timer() {
+            let timer = self.now + std::cmp::max(wait, std::time::Duration::from_millis(1));
+            if timer > end {
+                break;
+            }
+            self.now = timer;
+            clock::freeze(Some(self.now));
+            self.render()?;
+        }
+        self.now = end;
+        clock::freeze(Some(self.now));
+        self.render()
+    }
+
+    /// One iteration of the main loop, without waiting for input.
+    fn render(&mut self) -> std::io::Result<()> {
+        self.model.tick();
+        if self.model.needs_redraw {
+            self.model.needs_redraw = false;
+            self.terminal.draw(|frame| self.model.view(frame))?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for HeadlessRunner {
+    fn drop(&mut self) {
+        clock::freeze(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn runner() -> HeadlessRunner {
+        let args = crate::cli::Cli::parse_from(["ttl"]).run;
+        HeadlessRunner::new(model(&args).unwrap()).unwrap()
+    }
+
+    fn run(source: &str) -> std::io::Result<HeadlessRunner> {
+        let script = Script::parse(std::path::Path::new("inline.ttl"), source)?;
+        let mut runner = runner();
+        runner.run(&script)?;
+        Ok(runner)
+    }
+
+    /// Replays every script of `tests/scripts`, checking the screens against their snapshots.
+    #[test]
+    fn scripts() {
+        let directory = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("scripts");
+        let mut paths: Vec<_> = std::fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "ttl"))
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty());
+
+        for path in paths {
+            let script = Script::load(&path).unwrap();
+            if let Err(error) = runner().run(&script) {
+                panic!("{}", error);
+            }
+        }
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        let error = run("pace 10ms\n\nwait soon\n").err().unwrap();
+        assert_eq!(error.to_string(), "inline.ttl:3: invalid duration 'soon'");
+
+        let error = run("# comment\njump\n").err().unwrap();
+        assert_eq!(error.to_string(), "inline.ttl:2: unknown step 'jump'");
+
+        let error = run("key Hyper-x\n").err().unwrap();
+        assert_eq!(error.to_string(), "inline.ttl:1: unknown key 'Hyper-x'");
+    }
+
+    #[test]
+    fn failed_expectations_show_the_screen() {
+        let error = run("expect Corpus: Nowhere\n").err().unwrap().to_string();
+        assert!(error.starts_with("inline.ttl:1: expected 'Corpus: Nowhere' on the screen:\n"));
+        assert!(error.contains("Corpus: English Top"));
+    }
+
+    #[test]
+    fn missing_snapshots_fail() {
+        let error = run("snapshot missing\n").err().unwrap().to_string();
+        assert!(error.starts_with("inline.ttl:1: inline.missing.snap does not exist"));
+        assert!(!std::path::Path::new("inline.missing.snap").exists());
+    }
+
+    #[test]
+    fn exports_do_not_touch_user_data() {
+        run("type-target\nkey x\nexpect Set export_directory\n").unwrap();
+    }
+
+    #[test]
+    fn keystrokes_follow_the_pace() {
+        let runner = run("pace 200ms\ntype-target 11\n").unwrap();
+        let test = &runner.model.current_test;
+        assert_eq!(test.target_position(), 11);
+        assert_eq!(test.duration(), std::time::Duration::from_secs(2));
+        assert_eq!(test.accuracy(), 1.0);
+    }
+
+    #[test]
+    fn typed_text_is_unescaped() {
+        let runner = run("type  a\\\\b\\n\n").unwrap();
+        assert_eq!(runner.model.current_test.current_text, " a\\b\n");
+    }
+
+    #[test]
+    fn quitting_ends_the_script() {
+        let runner = run("key Esc\ntype abc\n").unwrap();
+        assert!(runner.model.should_quit);
+        assert_eq!(runner.model.current_test.current_text, "");
+    }
+}
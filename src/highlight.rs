@@ -0,0 +1,117 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Plain,
+    Keyword,
+    /// Identifiers starting with an uppercase letter, by convention types and constants.
+    Type,
+    String,
+    Comment,
+    Number,
+    Punctuation,
+}
+
+const RUST_KEYWORDS: [&str; 39] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "yield",
+];
+
+/// Classifies every grapheme of `text`, source code written in `language`, for syntax
+/// highlighting. This is a lexer for display only: it knows about comments, string literals,
+/// numbers and keywords, which is all a typist needs to tell the structure of a snippet apart.
+pub fn highlight(text: &str, language: Option<&str>) -> Vec<TokenKind> {
+    let graphemes: Vec<&str> = text.graphemes(true).collect();
+    let keywords: &[&str] = match language {
+        Some("Rust") => &RUST_KEYWORDS,
+        _ => &[],
+    };
+    let line_comment = match language {
+        Some("Python") => "#",
+        _ => "//",
+    };
+
+    let starts_with = |index: usize, prefix: &str| {
+        graphemes[index..]
+            .iter()
+            .zip(prefix.graphemes(true))
+            .filter(|(grapheme, prefix)| grapheme == &prefix)
+            .count()
+            == prefix.graphemes(true).count()
+    };
+    let is_identifier = |grapheme: &str| {
+        grapheme
+            .chars()
+            .all(|character| character.is_alphanumeric() || character == '_')
+    };
+
+    let mut kinds = Vec::with_capacity(graphemes.len());
+    let mut index = 0;
+
+    while index < graphemes.len() {
+        let grapheme = graphemes[index];
+
+        let (kind, length) = if starts_with(index, line_comment) {
+            let length = graphemes[index..]
+                .iter()
+                .take_while(|grapheme| **grapheme != "\n")
+                .count();
+            (TokenKind::Comment, length)
+        } else if starts_with(index, "/*") {
+            let length = (index + 2..graphemes.len())
+                .find(|&end| starts_with(end, "*/"))
+                .map_or(graphemes.len() - index, |end| end + 2 - index);
+            (TokenKind::Comment, length)
+        } else if grapheme == "\"" {
+            let mut end = index + 1;
+            while end < graphemes.len() && graphemes[end] != "\"" {
+                end += if graphemes[end] == "\\" { 2 } else { 1 };
+            }
+            (
+                TokenKind::String,
+                std::cmp::min(end + 1, graphemes.len()) - index,
+            )
+        } else if grapheme == "'" && graphemes.get(index + 2) == Some(&"'") {
+            (TokenKind::String, 3)
+        } else if grapheme == "'" && graphemes.get(index + 1) == Some(&"\\") {
+            let length = graphemes[index + 2..]
+                .iter()
+                .position(|grapheme| *grapheme == "'")
+                .map_or(graphemes.len() - index, |end| end + 3);
+            (TokenKind::String, length)
+        } else if grapheme.chars().all(|character| character.is_ascii_digit()) {
+            let length = graphemes[index..]
+                .iter()
+                .take_while(|grapheme| is_identifier(grapheme))
+                .count();
+            (TokenKind::Number, length)
+        } else if is_identifier(grapheme) {
+            let length = graphemes[index..]
+                .iter()
+                .take_while(|grapheme| is_identifier(grapheme))
+                .count();
+            let word = graphemes[index..index + length].concat();
+            let kind = if keywords.contains(&word.as_str()) {
+                TokenKind::Keyword
+            } else if word.starts_with(char::is_uppercase) {
+                TokenKind::Type
+            } else {
+                TokenKind::Plain
+            };
+            (kind, length)
+        } else if grapheme.trim().is_empty() {
+            (TokenKind::Plain, 1)
+        } else {
+            (TokenKind::Punctuation, 1)
+        };
+
+        let length = std::cmp::max(length, 1);
+        kinds.extend(std::iter::repeat(kind).take(length));
+        index += length;
+    }
+
+    kinds.truncate(graphemes.len());
+    kinds
+}
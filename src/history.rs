@@ -0,0 +1,381 @@
+use std::io::{BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    alignment::ErrorCounts,
+    model::{Screen, Strictness, Test, TimedInput},
+    statistics::{self, KeyStatistics},
+};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TestRecord {
+    pub corpus: String,
+    pub test_length: usize,
+    #[serde(default)]
+    pub time_limit: Option<std::time::Duration>,
+    pub target_text: String,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    pub wpm: f64,
+    pub raw_wpm: f64,
+    pub accuracy: f64,
+    /// See [`Test::completion`]. Defaults to a complete test for older records, as only finished
+    /// tests are recorded.
+    #[serde(default = "complete")]
+    pub completion: f64,
+    pub duration: std::time::Duration,
+    pub timestamp: std::time::SystemTime,
+    #[serde(default)]
+    pub key_statistics: std::collections::BTreeMap<String, KeyStatistics>,
+    #[serde(default)]
+    pub transition_statistics: std::collections::BTreeMap<String, KeyStatistics>,
+    #[serde(default)]
+    pub errors: ErrorCounts,
+    #[serde(default)]
+    pub word_by_word: bool,
+    #[serde(default)]
+    pub strictness: Strictness,
+    /// Empty for tests recorded before inputs were kept, which cannot be replayed.
+    #[serde(default)]
+    pub inputs: Vec<TimedInput>,
+}
+
+impl TestRecord {
+    pub fn new(test: &Test, corpus: &str, test_length: usize) -> Self {
+        TestRecord {
+            corpus: corpus.to_string(),
+            test_length,
+            time_limit: test.time_limit,
+            target_text: test.target_text.clone(),
+            seed: test.seed,
+            wpm: test.wpm(),
+            raw_wpm: test.raw_wpm(),
+            accuracy: test.accuracy(),
+            completion: test.completion(),
+            duration: test.duration(),
+            timestamp: std::time::SystemTime::now(),
+            key_statistics: statistics::key_statistics(test),
+            transition_statistics: statistics::transition_statistics(test),
+            errors: test.alignment().error_counts(),
+            word_by_word: test.word_by_word,
+            strictness: test.strictness,
+            inputs: test.inputs.clone(),
+        }
+    }
+
+    /// Word count of the test, or its time limit for timed tests.
+    pub fn length_label(&self) -> String {
+        match self.time_limit {
+            Some(time_limit) => format!("{}s", time_limit.as_secs()),
+            None => self.test_length.to_string(),
+        }
+    }
+}
+
+fn complete() -> f64 {
+    1.0
+}
+
+/// Finished tests, stored one JSON record per line so that new results can be appended without
+/// rewriting the whole file.
+pub struct History {
+    pub path: Option<std::path::PathBuf>,
+    pub records: Vec<TestRecord>,
+}
+
+impl History {
+    pub fn in_memory() -> Self {
+        History {
+            path: None,
+            records: Vec::new(),
+        }
+    }
+
+    pub fn default_path() -> Option<std::path::PathBuf> {
+        dirs::data_dir().map(|data_dir| data_dir.join("terminal_typing_lab").join("history.jsonl"))
+    }
+
+    /// Reads the records in `path`, skipping with a warning any line that is not one, such as a
+    /// record cut short by a crash, so that one bad line does not cost the whole history.
+    pub fn load(path: std::path::PathBuf) -> std::io::Result<Self> {
+        let mut records = Vec::new();
+
+        match std::fs::File::open(&path) {
+            Ok(file) => {
+                for (line_index, line) in std::io::BufReader::new(file).lines().enumerate() {
+                    let record = match line {
+                        Ok(line) if line.trim().is_empty() => continue,
+                        Ok(line) => serde_json::from_str(&line).map_err(|error| error.to_string()),
+                        Err(error) if error.kind() == std::io::ErrorKind::InvalidData => {
+                            Err(error.to_string())
+                        }
+                        Err(error) => return Err(error),
+                    };
+                    match record {
+                        Ok(record) => records.push(record),
+                        Err(error) => eprintln!(
+                            "warning: skipping {}:{}: {}",
+                            path.display(),
+                            line_index + 1,
+                            error
+                        ),
+                    }
+                }
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error),
+        }
+
+        Ok(History {
+            path: Some(path),
+            records,
+        })
+    }
+
+    pub fn record(&mut self, record: TestRecord) -> std::io::Result<()> {
+        let result = match &self.path {
+            Some(path) => append_record(path, &record),
+            None => Ok(()),
+        };
+        self.records.push(record);
+        result
+    }
+
+    /// Highest WPM reached in a test of the same corpus and length (or time limit).
+    pub fn personal_best(
+        &self,
+        corpus: &str,
+        test_length: usize,
+        time_limit: Option<std::time::Duration>,
+    ) -> Option<f64> {
+        self.records
+            .iter()
+            .filter(|record| {
+                record.corpus == corpus
+                    && record.time_limit == time_limit
+                    && (time_limit.is_some() || record.test_length == test_length)
+            })
+            .map(|record| record.wpm)
+            .max_by(f64::total_cmp)
+    }
+
+    /// Per-grapheme statistics summed over every recorded test.
+    pub fn key_statistics(&self) -> std::collections::BTreeMap<String, KeyStatistics> {
+        let mut total = std::collections::BTreeMap::new();
+        for record in &self.records {
+            statistics::merge_statistics(&mut total, &record.key_statistics);
+        }
+        total
+    }
+
+    /// Per-transition statistics summed over every recorded test.
+    pub fn transition_statistics(&self) -> std::collections::BTreeMap<String, KeyStatistics> {
+        let mut total = std::collections::BTreeMap::new();
+        for record in &self.records {
+            statistics::merge_statistics(&mut total, &record.transition_statistics);
+        }
+        total
+    }
+
+    pub fn corpora(&self) -> Vec<String> {
+        let mut corpora: Vec<String> = self
+            .records
+            .iter()
+            .map(|record| record.corpus.clone())
+            .collect();
+        corpora.sort();
+        corpora.dedup();
+        corpora
+    }
+}
+
+fn append_record(path: &std::path::Path, record: &TestRecord) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+
+    let line = serde_json::to_string(record)?;
+    writeln!(file, "{}", line)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HistorySort {
+    Date,
+    Wpm,
+    Accuracy,
+    Duration,
+}
+
+impl HistorySort {
+    pub fn next(self) -> Self {
+        match self {
+            HistorySort::Date => HistorySort::Wpm,
+            HistorySort::Wpm => HistorySort::Accuracy,
+            HistorySort::Accuracy => HistorySort::Duration,
+            HistorySort::Duration => HistorySort::Date,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HistorySort::Date => "Date",
+            HistorySort::Wpm => "WPM",
+            HistorySort::Accuracy => "Accuracy",
+            HistorySort::Duration => "Duration",
+        }
+    }
+}
+
+/// State of the history screen: how past runs are ordered, which corpus they are restricted to,
+/// which row is selected, and which screen it was opened from.
+pub struct HistoryBrowser {
+    pub sort: HistorySort,
+    pub descending: bool,
+    pub corpus_filter: Option<String>,
+    pub selected: usize,
+    /// Where going back from the history leads, so that results can be looked at again.
+    pub return_screen: Screen,
+}
+
+impl HistoryBrowser {
+    pub fn rows<'a>(&self, history: &'a History) -> Vec<&'a TestRecord> {
+        let mut rows: Vec<&TestRecord> = history
+            .records
+            .iter()
+            .filter(|record| {
+                self.corpus_filter
+                    .as_ref()
+                    .map_or(true, |corpus| &record.corpus == corpus)
+            })
+            .collect();
+
+        rows.sort_by(|a, b| {
+            let ordering = match self.sort {
+                HistorySort::Date => a.timestamp.cmp(&b.timestamp),
+                HistorySort::Wpm => a.wpm.total_cmp(&b.wpm),
+                HistorySort::Accuracy => a.accuracy.total_cmp(&b.accuracy),
+                HistorySort::Duration => a.duration.cmp(&b.duration),
+            };
+            if self.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+
+        rows
+    }
+
+    pub fn cycle_corpus_filter(&mut self, history: &History, forward: bool) {
+        let corpora = history.corpora();
+        let current = self
+            .corpus_filter
+            .as_ref()
+            .and_then(|corpus| corpora.iter().position(|c| c == corpus));
+
+        // Position 0 stands for "all corpora", the rest map onto the known corpus names.
+        let count = corpora.len() + 1;
+        let position = current.map_or(0, |index| index + 1);
+        let position = if forward {
+            (position + 1) % count
+        } else {
+            (position + count - 1) % count
+        };
+
+        self.corpus_filter = position.checked_sub(1).map(|index| corpora[index].clone());
+        self.selected = 0;
+    }
+}
+
+impl Default for HistoryBrowser {
+    fn default() -> Self {
+        HistoryBrowser {
+            sort: HistorySort::Date,
+            descending: true,
+            corpus_filter: None,
+            selected: 0,
+            return_screen: Screen::Test,
+        }
+    }
+}
+
+/// Formats a timestamp as `YYYY-MM-DD HH:MM` in UTC.
+pub fn format_timestamp(timestamp: std::time::SystemTime) -> String {
+    let (year, month, day, seconds_of_day) = civil_time(timestamp);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3_600,
+        seconds_of_day % 3_600 / 60
+    )
+}
+
+/// Formats a timestamp as RFC 3339 in UTC, `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn format_rfc3339(timestamp: std::time::SystemTime) -> String {
+    let (year, month, day, seconds_of_day) = civil_time(timestamp);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3_600,
+        seconds_of_day % 3_600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+/// Year, month, day and second of the day of a timestamp, in UTC.
+fn civil_time(timestamp: std::time::SystemTime) -> (i64, i64, i64, u64) {
+    let seconds = timestamp
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let days = (seconds / 86_400) as i64;
+    let seconds_of_day = seconds % 86_400;
+
+    // Civil-from-days conversion, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day, seconds_of_day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corrupt_lines_are_skipped() {
+        let path = std::env::temp_dir().join(format!("ttl-history-{}.jsonl", std::process::id()));
+        let record = serde_json::to_string(&TestRecord::new(&Test::new("ab"), "Test", 1)).unwrap();
+        let mut contents = format!("{record}\n{{\"corpus\": \"Te\n\n").into_bytes();
+        contents.extend_from_slice(b"\xff\xfe\n");
+        contents.extend_from_slice(format!("{record}\n").as_bytes());
+        std::fs::write(&path, contents).unwrap();
+
+        let history = History::load(path.clone());
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(history.unwrap().records.len(), 2);
+    }
+}
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyboardLayout {
+    Qwerty,
+    Dvorak,
+    Colemak,
+    Azerty,
+}
+
+/// A key of the main block, with the characters it produces without and with shift.
+#[derive(Clone, Copy)]
+pub struct Key {
+    pub normal: char,
+    pub shifted: char,
+}
+
+pub struct KeyboardRow {
+    /// Horizontal stagger of the row, in key widths.
+    pub offset: f64,
+    pub keys: Vec<Key>,
+}
+
+impl KeyboardLayout {
+    pub const ALL: [KeyboardLayout; 4] = [
+        KeyboardLayout::Qwerty,
+        KeyboardLayout::Dvorak,
+        KeyboardLayout::Colemak,
+        KeyboardLayout::Azerty,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyboardLayout::Qwerty => "QWERTY",
+            KeyboardLayout::Dvorak => "Dvorak",
+            KeyboardLayout::Colemak => "Colemak",
+            KeyboardLayout::Azerty => "AZERTY",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = KeyboardLayout::ALL
+            .iter()
+            .position(|&layout| layout == self)
+            .unwrap_or(0);
+        KeyboardLayout::ALL[(index + 1) % KeyboardLayout::ALL.len()]
+    }
+
+    pub fn previous(self) -> Self {
+        let index = KeyboardLayout::ALL
+            .iter()
+            .position(|&layout| layout == self)
+            .unwrap_or(0);
+        KeyboardLayout::ALL[(index + KeyboardLayout::ALL.len() - 1) % KeyboardLayout::ALL.len()]
+    }
+
+    /// Rows of the main block from top to bottom, the space bar excluded.
+    pub fn rows(self) -> Vec<KeyboardRow> {
+        let rows: [(&str, &str); 4] = match self {
+            KeyboardLayout::Qwerty => [
+                ("`1234567890-=", "~!@#$%^&*()_+"),
+                ("qwertyuiop[]\\", "QWERTYUIOP{}|"),
+                ("asdfghjkl;'", "ASDFGHJKL:\""),
+                ("zxcvbnm,./", "ZXCVBNM<>?"),
+            ],
+            KeyboardLayout::Dvorak => [
+                ("`1234567890[]", "~!@#$%^&*(){}"),
+                ("',.pyfgcrl/=\\", "\"<>PYFGCRL?+|"),
+                ("aoeuidhtns-", "AOEUIDHTNS_"),
+                (";qjkxbmwvz", ":QJKXBMWVZ"),
+            ],
+            KeyboardLayout::Colemak => [
+                ("`1234567890-=", "~!@#$%^&*()_+"),
+                ("qwfpgjluy;[]\\", "QWFPGJLUY:{}|"),
+                ("arstdhneio'", "ARSTDHNEIO\""),
+                ("zxcvbkm,./", "ZXCVBKM<>?"),
+            ],
+            KeyboardLayout::Azerty => [
+                ("²&é\"'(-è_çà)=", "²1234567890°+"),
+                ("azertyuiop^$", "AZERTYUIOP¨£"),
+                ("qsdfghjklmù*", "QSDFGHJKLM%µ"),
+                ("wxcvbn,;:!", "WXCVBN?./§"),
+            ],
+        };
+
+        rows.iter()
+            .zip([0.0, 1.5, 1.75, 2.25])
+            .map(|((normal, shifted), offset)| KeyboardRow {
+                offset,
+                keys: normal
+                    .chars()
+                    .zip(shifted.chars())
+                    .map(|(normal, shifted)| Key { normal, shifted })
+                    .collect(),
+            })
+            .collect()
+    }
+}
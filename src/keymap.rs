@@ -0,0 +1,804 @@
+use crossterm::event::{KeyCode, KeyModifiers};
+
+use crate::{action::Action, model::Screen};
+
+/// A key together with the modifiers held down with it. Shift is folded into characters and
+/// Shift-Tab, which terminals report inconsistently.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub modifiers: KeyModifiers,
+    pub code: KeyCode,
+}
+
+impl KeyBinding {
+    const fn new(modifiers: KeyModifiers, code: KeyCode) -> Self {
+        KeyBinding { modifiers, code }
+    }
+
+    const fn key(code: KeyCode) -> Self {
+        KeyBinding::new(KeyModifiers::NONE, code)
+    }
+
+    const fn control(character: char) -> Self {
+        KeyBinding::new(KeyModifiers::CONTROL, KeyCode::Char(character))
+    }
+
+    pub fn from_event(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut modifiers =
+            modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        if matches!(code, KeyCode::Char(_) | KeyCode::BackTab) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        KeyBinding { modifiers, code }
+    }
+
+    /// Whether the key types a character on the test screen.
+    fn is_typed(&self) -> bool {
+        self.modifiers.is_empty() && matches!(self.code, KeyCode::Char(_))
+    }
+}
+
+const NAMED_KEYS: [(&str, KeyCode); 15] = [
+    ("Backspace", KeyCode::Backspace),
+    ("Enter", KeyCode::Enter),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Tab", KeyCode::Tab),
+    ("Delete", KeyCode::Delete),
+    ("Insert", KeyCode::Insert),
+    ("Esc", KeyCode::Esc),
+    ("Space", KeyCode::Char(' ')),
+];
+
+impl std::fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "Control-"),
+            (KeyModifiers::ALT, "Alt-"),
+            (KeyModifiers::SHIFT, "Shift-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+
+        match self.code {
+            KeyCode::BackTab => f.write_str("Shift-Tab"),
+            KeyCode::F(number) => write!(f, "F{}", number),
+            code => match NAMED_KEYS.iter().find(|(_, named)| *named == code) {
+                Some((name, _)) => f.write_str(name),
+                None => match code {
+                    KeyCode::Char(character) => write!(f, "{}", character),
+                    _ => f.write_str("?"),
+                },
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for KeyBinding {
+    type Err = String;
+
+    /// Parses keys written like `Control-Backspace`, `Shift-Tab`, `Alt-x` or `F5`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        'modifiers: loop {
+            for (prefix, modifier) in [
+                ("control-", KeyModifiers::CONTROL),
+                ("ctrl-", KeyModifiers::CONTROL),
+                ("alt-", KeyModifiers::ALT),
+                ("shift-", KeyModifiers::SHIFT),
+            ] {
+                if rest.len() > prefix.len()
+                    && rest.is_char_boundary(prefix.len())
+                    && rest[..prefix.len()].eq_ignore_ascii_case(prefix)
+                {
+                    modifiers |= modifier;
+                    rest = &rest[prefix.len()..];
+                    continue 'modifiers;
+                }
+            }
+            break;
+        }
+
+        let mut characters = rest.chars();
+        let code = match (characters.next(), characters.next()) {
+            (Some(character), None) => KeyCode::Char(character),
+            _ => NAMED_KEYS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(rest))
+                .map(|(_, code)| *code)
+                .or_else(|| {
+                    let number = rest.strip_prefix(['F', 'f'])?.parse().ok()?;
+                    (1..=24).contains(&number).then_some(KeyCode::F(number))
+                })
+                .ok_or_else(|| format!("unknown key '{}'", text))?,
+        };
+
+        let code = match code {
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            KeyCode::Char(character) if modifiers.contains(KeyModifiers::SHIFT) => {
+                KeyCode::Char(character.to_uppercase().next().unwrap_or(character))
+            }
+            code => code,
+        };
+
+        Ok(KeyBinding::from_event(code, modifiers))
+    }
+}
+
+/// An action of one screen and the keys bound to it.
+pub struct Command {
+    /// Name of the command in the `keys` tables of the configuration file.
+    pub name: &'static str,
+    pub action: Action,
+    /// Shown in the help text.
+    pub description: &'static str,
+    pub keys: Vec<KeyBinding>,
+}
+
+impl Command {
+    fn new(
+        name: &'static str,
+        action: Action,
+        description: &'static str,
+        keys: &[KeyBinding],
+    ) -> Self {
+        Command {
+            name,
+            action,
+            description,
+            keys: keys.to_vec(),
+        }
+    }
+}
+
+pub struct ScreenKeymap {
+    pub commands: Vec<Command>,
+    /// Names of the commands on each line of the help text.
+    help: &'static [&'static [&'static str]],
+}
+
+/// Changes to the default key bindings, as written in the configuration file: for every screen,
+/// the commands whose keys are replaced.
+pub type KeymapOverrides =
+    std::collections::BTreeMap<String, std::collections::BTreeMap<String, Vec<String>>>;
+
+/// Maps key presses to actions, for every screen.
+pub struct Keymap {
+    test: ScreenKeymap,
+    results: ScreenKeymap,
+    history: ScreenKeymap,
+    heatmap: ScreenKeymap,
+    replay: ScreenKeymap,
+    race: ScreenKeymap,
+    /// Kept to save them back with the configuration.
+    pub overrides: KeymapOverrides,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use KeyCode::*;
+        let key = KeyBinding::key;
+        let control = KeyBinding::control;
+        let quit = [key(Esc), control('c'), control('q')];
+
+        Keymap {
+            test: ScreenKeymap {
+                commands: vec![
+                    Command::new(
+                        "delete-character",
+                        Action::DeleteCharacter,
+                        "delete character",
+                        &[key(Backspace), control('h')],
+                    ),
+                    Command::new(
+                        "delete-word",
+                        Action::DeleteWord,
+                        "delete word",
+                        &[
+                            KeyBinding::new(KeyModifiers::CONTROL, Backspace),
+                            control('w'),
+                        ],
+                    ),
+                    Command::new("restart", Action::Restart, "restart", &[key(Tab)]),
+                    Command::new(
+                        "next-test",
+                        Action::NextTest,
+                        "next test",
+                        &[key(Enter), control('n')],
+                    ),
+                    Command::new(
+                        "previous-corpus",
+                        Action::PreviousCorpus,
+                        "previous corpus",
+                        &[key(Left)],
+                    ),
+                    Command::new(
+                        "next-corpus",
+                        Action::NextCorpus,
+                        "next corpus",
+                        &[key(Right)],
+                    ),
+                    Command::new(
+                        "increase-length",
+                        Action::IncreaseTestLength,
+                        "increase test length or duration",
+                        &[key(Up)],
+                    ),
+                    Command::new(
+                        "decrease-length",
+                        Action::DecreaseTestLength,
+                        "decrease test length or duration",
+                        &[key(Down)],
+                    ),
+                    Command::new(
+                        "next-sampling",
+                        Action::NextSamplingMethod,
+                        "change word sampling",
+                        &[control('p')],
+                    ),
+                    Command::new(
+                        "toggle-test-mode",
+                        Action::ToggleTestMode,
+                        "toggle timed tests",
+                        &[control('t')],
+                    ),
+                    Command::new(
+                        "toggle-word-by-word",
+                        Action::ToggleWordByWord,
+                        "toggle word-by-word mode",
+                        &[control('o')],
+                    ),
+                    Command::new(
+                        "next-strictness",
+                        Action::NextStrictness,
+                        "change error strictness",
+                        &[control('e')],
+                    ),
+                    Command::new(
+                        "next-theme",
+                        Action::NextTheme,
+                        "change theme",
+                        &[control('y')],
+                    ),
+                    Command::new(
+                        "toggle-ghost",
+                        Action::ToggleGhost,
+                        "race the best past run",
+                        &[control('g')],
+                    ),
+                    Command::new(
+                        "toggle-live-statistics",
+                        Action::ToggleLiveTypingStatistics,
+                        "toggle live typing statistics",
+                        &[control('l')],
+                    ),
+                    Command::new(
+                        "show-history",
+                        Action::ToggleHistory,
+                        "show history",
+                        &[control('r')],
+                    ),
+                    Command::new(
+                        "show-heatmap",
+                        Action::ToggleHeatmap,
+                        "show key heatmap",
+                        &[control('k')],
+                    ),
+                    Command::new(
+                        "toggle-frame-statistics",
+                        Action::ToggleFrameStatistics,
+                        "toggle frame statistics",
+                        &[control('s')],
+                    ),
+                    Command::new("quit", Action::Quit, "quit", &quit),
+                ],
+                help: &[
+                    &["delete-character", "delete-word"],
+                    &["restart", "next-test"],
+                    &["previous-corpus", "next-corpus"],
+                    &["increase-length", "decrease-length"],
+                    &["toggle-test-mode", "next-sampling"],
+                    &["toggle-word-by-word", "next-strictness"],
+                    &["next-theme", "toggle-ghost"],
+                    &["show-history", "show-heatmap"],
+                    &["toggle-live-statistics", "toggle-frame-statistics"],
+                    &["quit"],
+                ],
+            },
+            results: ScreenKeymap {
+                commands: vec![
+                    Command::new(
+                        "previous-tab",
+                        Action::PreviousResultsTab,
+                        "previous tab",
+                        &[key(Left)],
+                    ),
+                    Command::new(
+                        "next-tab",
+                        Action::NextResultsTab,
+                        "next tab",
+                        &[key(Right)],
+                    ),
+                    Command::new("next-test", Action::NextTest, "next test", &[key(Enter)]),
+                    Command::new("restart", Action::Restart, "restart", &[key(Tab)]),
+                    Command::new("replay", Action::ReplayTest, "replay", &[key(Char('p'))]),
+                    Command::new("export", Action::ExportResults, "export", &[key(Char('x'))]),
+                    Command::new("back", Action::DismissResults, "back to test", &[key(Esc)]),
+                    Command::new(
+                        "show-history",
+                        Action::ToggleHistory,
+                        "show history",
+                        &[control('r')],
+                    ),
+                    Command::new(
+                        "toggle-frame-statistics",
+                        Action::ToggleFrameStatistics,
+                        "toggle frame statistics",
+                        &[control('s')],
+                    ),
+                    Command::new("quit", Action::Quit, "quit", &quit[1..]),
+                ],
+                help: &[
+                    &["previous-tab", "next-tab"],
+                    &["next-test", "restart", "replay", "export"],
+                    &["back", "show-history"],
+                    &["quit"],
+                ],
+            },
+            history: ScreenKeymap {
+                commands: vec![
+                    Command::new(
+                        "previous-entry",
+                        Action::SelectPreviousHistoryEntry,
+                        "previous test",
+                        &[key(Up)],
+                    ),
+                    Command::new(
+                        "next-entry",
+                        Action::SelectNextHistoryEntry,
+                        "next test",
+                        &[key(Down)],
+                    ),
+                    Command::new(
+                        "previous-corpus",
+                        Action::PreviousHistoryCorpus,
+                        "previous corpus",
+                        &[key(Left)],
+                    ),
+                    Command::new(
+                        "next-corpus",
+                        Action::NextHistoryCorpus,
+                        "next corpus",
+                        &[key(Right)],
+                    ),
+                    Command::new(
+                        "next-sort",
+                        Action::NextHistorySort,
+                        "change sorting",
+                        &[key(Tab)],
+                    ),
+                    Command::new(
+                        "reverse-sort",
+                        Action::ReverseHistorySort,
+                        "reverse sorting",
+                        &[key(BackTab)],
+                    ),
+                    Command::new(
+                        "replay",
+                        Action::ReplayTest,
+                        "replay test",
+                        &[key(Enter), key(Char('p'))],
+                    ),
+                    Command::new("race", Action::RaceTest, "race test", &[key(Char('g'))]),
+                    Command::new(
+                        "export",
+                        Action::ExportResults,
+                        "export listed tests",
+                        &[key(Char('x'))],
+                    ),
+                    Command::new(
+                        "back",
+                        Action::ToggleHistory,
+                        "back",
+                        &[key(Esc), control('r')],
+                    ),
+                    Command::new(
+                        "toggle-frame-statistics",
+                        Action::ToggleFrameStatistics,
+                        "toggle frame statistics",
+                        &[control('s')],
+                    ),
+                    Command::new("quit", Action::Quit, "quit", &quit[1..]),
+                ],
+                help: &[
+                    &["previous-entry", "next-entry"],
+                    &["previous-corpus", "next-corpus"],
+                    &["next-sort", "reverse-sort"],
+                    &["replay", "race", "export"],
+                    &["back"],
+                ],
+            },
+            heatmap: ScreenKeymap {
+                commands: vec![
+                    Command::new(
+                        "previous-layout",
+                        Action::PreviousKeyboardLayout,
+                        "previous layout",
+                        &[key(Left)],
+                    ),
+                    Command::new(
+                        "next-layout",
+                        Action::NextKeyboardLayout,
+                        "next layout",
+                        &[key(Right)],
+                    ),
+                    Command::new(
+                        "next-metric",
+                        Action::NextHeatmapMetric,
+                        "switch between error rate and latency",
+                        &[key(Tab)],
+                    ),
+                    Command::new(
+                        "back",
+                        Action::ToggleHeatmap,
+                        "back to test",
+                        &[key(Esc), control('k')],
+                    ),
+                    Command::new(
+                        "toggle-frame-statistics",
+                        Action::ToggleFrameStatistics,
+                        "toggle frame statistics",
+                        &[control('s')],
+                    ),
+                    Command::new("quit", Action::Quit, "quit", &quit[1..]),
+                ],
+                help: &[
+                    &["previous-layout", "next-layout", "next-metric"],
+                    &["back"],
+                    &["quit"],
+                ],
+            },
+            replay: ScreenKeymap {
+                commands: vec![
+                    Command::new(
+                        "pause",
+                        Action::ToggleReplayPause,
+                        "pause or resume",
+                        &[key(Char(' '))],
+                    ),
+                    Command::new(
+                        "step-backward",
+                        Action::StepReplayBackward,
+                        "step back",
+                        &[key(Left)],
+                    ),
+                    Command::new(
+                        "step-forward",
+                        Action::StepReplayForward,
+                        "step forward",
+                        &[key(Right)],
+                    ),
+                    Command::new(
+                        "slower",
+                        Action::DecreaseReplaySpeed,
+                        "slower",
+                        &[key(Down)],
+                    ),
+                    Command::new("faster", Action::IncreaseReplaySpeed, "faster", &[key(Up)]),
+                    Command::new("restart", Action::RestartReplay, "restart", &[key(Tab)]),
+                    Command::new("back", Action::CloseReplay, "back", &[key(Esc)]),
+                    Command::new(
+                        "toggle-frame-statistics",
+                        Action::ToggleFrameStatistics,
+                        "toggle frame statistics",
+                        &[control('s')],
+                    ),
+                    Command::new("quit", Action::Quit, "quit", &quit[1..]),
+                ],
+                help: &[
+                    &["pause", "restart"],
+                    &["step-backward", "step-forward"],
+                    &["slower", "faster"],
+                    &["back"],
+                ],
+            },
+            race: ScreenKeymap {
+                commands: vec![
+                    Command::new(
+                        "start-race",
+                        Action::StartRace,
+                        "start the race",
+                        &[key(Enter)],
+                    ),
+                    Command::new(
+                        "toggle-frame-statistics",
+                        Action::ToggleFrameStatistics,
+                        "toggle frame statistics",
+                        &[control('s')],
+                    ),
+                    Command::new("quit", Action::Quit, "leave the race", &quit),
+                ],
+                help: &[&["start-race"], &["quit"]],
+            },
+            overrides: KeymapOverrides::new(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Names of the screens in the `keys` tables of the configuration file.
+    const SCREENS: [(&'static str, Screen); 6] = [
+        ("test", Screen::Test),
+        ("results", Screen::Results),
+        ("history", Screen::History),
+        ("heatmap", Screen::Heatmap),
+        ("replay", Screen::Replay),
+        ("race", Screen::Race),
+    ];
+
+    /// The default keymap with the keys of the commands in `overrides` replaced. Fails on unknown
+    /// screens, commands or keys, and lists every conflict between the resulting bindings.
+    pub fn new(overrides: KeymapOverrides) -> Result<Keymap, String> {
+        let mut keymap = Keymap::default();
+
+        for (screen_name, commands) in &overrides {
+            let screen = Keymap::SCREENS
+                .iter()
+                .find(|(name, _)| name == screen_name)
+                .map(|(_, screen)| *screen)
+                .ok_or_else(|| format!("unknown screen '{}' in keys", screen_name))?;
+            let screen_keymap = keymap.screen_mut(screen);
+
+            for (command_name, keys) in commands {
+                let command = screen_keymap
+                    .commands
+                    .iter_mut()
+                    .find(|command| command.name == command_name)
+                    .ok_or_else(|| {
+                        format!(
+                            "unknown command '{}' on the {} screen",
+                            command_name, screen_name
+                        )
+                    })?;
+                command.keys = keys
+                    .iter()
+                    .map(|key| key.parse())
+                    .collect::<Result<_, _>>()?;
+            }
+        }
+
+        let conflicts = keymap.conflicts();
+        if !conflicts.is_empty() {
+            return Err(format!("conflicting keys: {}", conflicts.join("; ")));
+        }
+
+        keymap.overrides = overrides;
+        Ok(keymap)
+    }
+
+    /// Keys bound to several commands of a screen, printable keys that would keep the typist
+    /// from typing them, and screens left without a way to quit.
+    pub fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = Vec::new();
+
+        for (screen_name, screen) in Keymap::SCREENS {
+            let commands = &self.screen(screen).commands;
+            let bindings: Vec<(&KeyBinding, &Command)> = commands
+                .iter()
+                .flat_map(|command| command.keys.iter().map(move |key| (key, command)))
+                .collect();
+
+            for (index, (key, command)) in bindings.iter().enumerate() {
+                if bindings[..index].iter().any(|(other, _)| other == key) {
+                    continue;
+                }
+                let names: Vec<&str> = bindings[index..]
+                    .iter()
+                    .filter(|(other, _)| other == key)
+                    .map(|(_, command)| command.name)
+                    .collect();
+                if names.len() > 1 {
+                    conflicts.push(format!(
+                        "{} is bound to {} on the {} screen",
+                        key,
+                        names.join(" and "),
+                        screen_name
+                    ));
+                }
+                if screen == Screen::Test && key.is_typed() {
+                    conflicts.push(format!(
+                        "{} cannot be bound to {} on the test screen, it has to be typed",
+                        key, command.name
+                    ));
+                }
+            }
+
+            if !commands
+                .iter()
+                .any(|command| command.action == Action::Quit && !command.keys.is_empty())
+            {
+                conflicts.push(format!("quit has no key on the {} screen", screen_name));
+            }
+        }
+
+        conflicts
+    }
+
+    pub fn screen(&self, screen: Screen) -> &ScreenKeymap {
+        match screen {
+            Screen::Test => &self.test,
+            Screen::Results => &self.results,
+            Screen::History => &self.history,
+            Screen::Heatmap => &self.heatmap,
+            Screen::Replay => &self.replay,
+            Screen::Race => &self.race,
+        }
+    }
+
+    fn screen_mut(&mut self, screen: Screen) -> &mut ScreenKeymap {
+        match screen {
+            Screen::Test => &mut self.test,
+            Screen::Results => &mut self.results,
+            Screen::History => &mut self.history,
+            Screen::Heatmap => &mut self.heatmap,
+            Screen::Replay => &mut self.replay,
+            Screen::Race => &mut self.race,
+        }
+    }
+
+    /// The action of `key` on `screen`. On the test screen, unbound characters are typed, and so
+    /// is Enter when `accepts_newlines`.
+    pub fn action(
+        &self,
+        screen: Screen,
+        key: KeyBinding,
+        accepts_newlines: bool,
+    ) -> Option<Action> {
+        if screen == Screen::Test && accepts_newlines && key == KeyBinding::key(KeyCode::Enter) {
+            return Some(Action::CharacterInput('\n'));
+        }
+
+        if let Some(command) = self
+            .screen(screen)
+            .commands
+            .iter()
+            .find(|command| command.keys.contains(&key))
+        {
+            return Some(command.action);
+        }
+
+        match key.code {
+            KeyCode::Char(character) if screen == Screen::Test && key.is_typed() => {
+                Some(Action::CharacterInput(character))
+            }
+            _ => None,
+        }
+    }
+
+    /// Help text of `screen`, one entry per line, holding every command shown on that line with
+    /// its keys. Commands without keys are left out, and so is Enter when it types newlines.
+    pub fn help(
+        &self,
+        screen: Screen,
+        accepts_newlines: bool,
+    ) -> Vec<Vec<(&Command, Vec<KeyBinding>)>> {
+        let screen_keymap = self.screen(screen);
+        let enter_types = screen == Screen::Test && accepts_newlines;
+
+        screen_keymap
+            .help
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .filter_map(|name| {
+                        let command = screen_keymap
+                            .commands
+                            .iter()
+                            .find(|command| command.name == *name)?;
+                        let keys: Vec<KeyBinding> = command
+                            .keys
+                            .iter()
+                            .copied()
+                            .filter(|key| !(enter_types && *key == KeyBinding::key(KeyCode::Enter)))
+                            .collect();
+                        (!keys.is_empty()).then_some((command, keys))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(entries: &[(&str, &str, &[&str])]) -> KeymapOverrides {
+        let mut overrides = KeymapOverrides::new();
+        for (screen, command, keys) in entries {
+            overrides.entry(screen.to_string()).or_default().insert(
+                command.to_string(),
+                keys.iter().map(|key| key.to_string()).collect(),
+            );
+        }
+        overrides
+    }
+
+    #[test]
+    fn keys_round_trip() {
+        for text in [
+            "Shift-Tab",
+            "Control--",
+            "Space",
+            "F24",
+            "Control-Alt-Backspace",
+            "A",
+        ] {
+            let key: KeyBinding = text.parse().unwrap();
+            assert_eq!(key.to_string(), text);
+        }
+
+        let parsed = |text: &str| text.parse::<KeyBinding>().unwrap().to_string();
+        assert_eq!(parsed("ctrl-x"), "Control-x");
+        assert_eq!(parsed("shift-a"), "A");
+        assert_eq!(parsed("shift-tab"), "Shift-Tab");
+        assert_eq!(parsed("-"), "-");
+        assert!("Shift-Tab".parse() == Ok(KeyBinding::key(KeyCode::BackTab)));
+
+        for text in ["", "F0", "F25", "Hyper-x", "Control-", "Enterr"] {
+            assert_eq!(
+                text.parse::<KeyBinding>().err(),
+                Some(format!("unknown key '{}'", text))
+            );
+        }
+    }
+
+    #[test]
+    fn default_keys_do_not_conflict() {
+        assert!(Keymap::default().conflicts().is_empty());
+    }
+
+    #[test]
+    fn conflicts_are_listed() {
+        let error = Keymap::new(overrides(&[
+            ("results", "replay", &["Tab"]),
+            ("test", "restart", &["x"]),
+            ("race", "quit", &[]),
+        ]))
+        .err()
+        .unwrap();
+        assert_eq!(
+            error,
+            "conflicting keys: x cannot be bound to restart on the test screen, it has to be \
+             typed; Tab is bound to restart and replay on the results screen; quit has no key on \
+             the race screen"
+        );
+
+        assert_eq!(
+            Keymap::new(overrides(&[("test", "fly", &["x"])])).err(),
+            Some("unknown command 'fly' on the test screen".to_string())
+        );
+        assert_eq!(
+            Keymap::new(overrides(&[("lobby", "quit", &["q"])])).err(),
+            Some("unknown screen 'lobby' in keys".to_string())
+        );
+    }
+
+    #[test]
+    fn overrides_replace_the_default_keys() {
+        let keymap = Keymap::new(overrides(&[("results", "replay", &["r"])])).unwrap();
+        let action = |key: &str| keymap.action(Screen::Results, key.parse().unwrap(), false);
+        assert!(action("r") == Some(Action::ReplayTest));
+        assert!(action("p").is_none());
+    }
+}
@@ -1,32 +1,195 @@
+mod cli;
+mod config;
 mod corpus;
+mod headless;
 
 pub mod action;
+pub mod alignment;
+pub mod clock;
+pub mod export;
+pub mod highlight;
+pub mod history;
+pub mod keyboard;
+pub mod keymap;
 pub mod model;
+pub mod race;
+pub mod replay;
+pub mod statistics;
 pub mod terminal;
+pub mod test_code;
+pub mod theme;
 pub mod view;
 
-use model::Model;
+use clap::{CommandFactory, Parser};
+use cli::{Cli, Command, CorporaCommand, RaceCommand, RunArgs, ScriptArgs};
+use corpus::Corpus;
+use history::History;
+use model::{Config, Model};
+use theme::Theme;
 
 use terminal::*;
 
-fn main() -> std::io::Result<()> {
-    let mut model = Model::default();
+fn main() -> std::process::ExitCode {
+    let cli = Cli::parse();
 
+    let result = match cli.command.unwrap_or(Command::Run(cli.run)) {
+        Command::Run(args) => run(&args),
+        Command::History(args) => load_history().map(|history| cli::print_history(&history, &args)),
+        Command::Export(args) => {
+            load_history().and_then(|history| cli::export_history(&history, &args))
+        }
+        Command::Corpora {
+            command: CorporaCommand::List,
+        } => load_corpora().map(|corpora| cli::print_corpora(&corpora)),
+        Command::Script(args) => run_script(&args),
+        Command::Race { command } => race(command),
+    };
+
+    match result {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &RunArgs) -> std::io::Result<()> {
+    let (corpora, themes) = (load_corpora()?, load_themes()?);
+    let config_path = Config::default_path();
+    let mut config = load_config(&corpora, &themes)?;
+    let saved_config = config.snapshot();
+    args.apply(&mut config, &corpora, &themes)?;
+
+    let mut model = Model::new(config, corpora, themes, load_history()?);
+    if let Some(code) = &args.code {
+        model.start_test_from_code(code)?;
+    }
+    let launched_config = model.config.snapshot();
+
+    interact(&mut model)?;
+
+    if let Some(config_path) = &config_path {
+        model
+            .config
+            .save_changes(config_path, &saved_config, &launched_config)?;
+    }
+
+    Ok(())
+}
+
+/// Hosts or joins a race. Settings changed during a race are not saved.
+fn race(command: RaceCommand) -> std::io::Result<()> {
+    let (corpora, themes) = (load_corpora()?, load_themes()?);
+    let mut config = load_config(&corpora, &themes)?;
+
+    let (address, name, listener) = match command {
+        RaceCommand::Host(args) => {
+            if args.run.code.is_some() {
+                let mut command = Cli::command();
+                command.build();
+                command
+                    .find_subcommand_mut("race")
+                    .and_then(|race| race.find_subcommand_mut("host"))
+                    .expect("the race host subcommand exists")
+                    .error(
+                        clap::error::ErrorKind::ArgumentConflict,
+                        "--code cannot be used when hosting, the host picks the text of every race",
+                    )
+                    .exit();
+            }
+            args.run.apply(&mut config, &corpora, &themes)?;
+            let listener = race::Listener::bind(&args.address)?;
+            (listener.local_address()?, args.name, Some(listener))
+        }
+        RaceCommand::Join(args) => (args.address, args.name, None),
+    };
+    #[cfg(unix)]
+    let is_hosting = listener.is_some();
+    if let Some(listener) = listener {
+        race::serve(listener);
+    }
+
+    let name = name.unwrap_or_else(race::default_name);
+    let mut model = Model::new(config, corpora, themes, load_history()?);
+    let result = race::RaceClient::connect(&address, &name).and_then(|client| {
+        model.join_race(client);
+        interact(&mut model)
+    });
+
+    #[cfg(unix)]
+    if let (true, race::Address::Unix(path)) = (is_hosting, &address) {
+        let _ = std::fs::remove_file(path);
+    }
+
+    result
+}
+
+/// Runs the terminal interface until the typist quits.
+fn interact(model: &mut Model) -> std::io::Result<()> {
     let mut terminal = create_terminal()?;
+    set_caret_style(model.config.caret_style, model.config.caret_blink)?;
 
     while !model.should_quit {
-        model.frame_statistics.new_frame();
+        model.tick();
+
+        if model.needs_redraw {
+            model.needs_redraw = false;
+            model.frame_statistics.new_frame();
+
+            terminal.draw(|frame| {
+                model.view(frame);
+            })?;
 
-        terminal.draw(|frame| {
-            model.view(frame);
-        })?;
+            model.frame_statistics.end_frame();
+        }
 
-        if let Some(action) = poll_terminal() {
-            model.update(action);
+        if let Some(timed_action) = poll_terminal(
+            &model.config.keymap,
+            model.screen,
+            model.current_test.accepts_newlines(),
+            model.time_until_next_timer(),
+        ) {
+            model.update(timed_action);
         }
     }
 
-    destroy_terminal()?;
+    destroy_terminal()
+}
 
-    Ok(())
+fn run_script(args: &ScriptArgs) -> std::io::Result<()> {
+    let script = headless::Script::load(&args.path)?;
+    let mut runner = headless::HeadlessRunner::new(headless::model(&args.run)?)?;
+    runner.update_snapshots = args.update_snapshots;
+    runner.run(&script)
+}
+
+fn load_config(corpora: &[Corpus], themes: &[Theme]) -> std::io::Result<Config> {
+    match Config::default_path() {
+        Some(config_path) => Config::load(&config_path, corpora, themes),
+        None => Ok(Config::default()),
+    }
+}
+
+fn load_corpora() -> std::io::Result<Vec<Corpus>> {
+    let mut corpora = Corpus::all_embedded();
+    if let Some(corpora_directory) = Corpus::user_directory() {
+        corpora.extend(Corpus::load_directory(&corpora_directory)?);
+    }
+    Ok(corpora)
+}
+
+fn load_themes() -> std::io::Result<Vec<Theme>> {
+    let mut themes = Theme::all_builtin();
+    if let Some(themes_directory) = Theme::user_directory() {
+        themes.extend(Theme::load_directory(&themes_directory)?);
+    }
+    Ok(themes)
+}
+
+fn load_history() -> std::io::Result<History> {
+    match History::default_path() {
+        Some(history_path) => History::load(history_path),
+        None => Ok(History::in_memory()),
+    }
 }
@@ -1,116 +1,1016 @@
-use crate::{action::Action, corpus::*};
+use crate::{
+    action::{Action, TimedAction},
+    alignment::{self, Alignment, ErrorKind, Step},
+    clock,
+    corpus::*,
+    export::{self, Export},
+    highlight::{self, TokenKind},
+    history::{History, HistoryBrowser, TestRecord},
+    keyboard::KeyboardLayout,
+    keymap::Keymap,
+    race::{Message, RaceClient, RaceEvent, RaceSettings},
+    replay::Replay,
+    statistics::{self, KeyStatistics, PracticeWeights, TestResults},
+    test_code::TestCode,
+    theme::Theme,
+};
 
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
 use unicode_normalization::UnicodeNormalization;
 use unicode_segmentation::UnicodeSegmentation;
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Screen {
+    Test,
+    Results,
+    History,
+    Heatmap,
+    Replay,
+    /// The players of a race, waiting for it to start or racing.
+    Race,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HeatmapMetric {
+    ErrorRate,
+    Latency,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ResultsTab {
+    Summary,
+    Errors,
+    Words,
+}
+
+impl ResultsTab {
+    pub const ALL: [ResultsTab; 3] = [ResultsTab::Summary, ResultsTab::Errors, ResultsTab::Words];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ResultsTab::Summary => "Summary",
+            ResultsTab::Errors => "Errors",
+            ResultsTab::Words => "Slowest Words",
+        }
+    }
+}
+
 pub struct Model {
     pub frame_statistics: FrameStatistics,
     pub config: Config,
     pub should_quit: bool,
+    /// Set whenever the model changes in a way the view has to reflect.
+    pub needs_redraw: bool,
+    next_timer: Option<std::time::Instant>,
+    /// Where the caret is drawn, which lags behind [`Test::caret_index`] while a smooth caret
+    /// moves.
+    pub displayed_caret: usize,
+    caret_moved_at: std::time::Instant,
     pub current_test: Test,
+    /// Name of the corpus the current test was taken from. Usually the configured one, but
+    /// races and past runs may bring their own without changing the configuration.
+    pub test_corpus: String,
+    /// Number of words, or lines of code, of the current test, see [`Config::test_length`].
+    pub test_length: usize,
+    pub corpora: Vec<Corpus>,
+    pub themes: Vec<Theme>,
+    pub screen: Screen,
+    pub history: History,
+    pub history_browser: HistoryBrowser,
+    pub results_tab: ResultsTab,
+    /// Best WPM for the finished test's corpus and length, from before it was recorded.
+    pub previous_personal_best: Option<f64>,
+    /// Statistics of the finished test for the results screen.
+    pub results: TestResults,
+    /// The test being played back on the replay screen.
+    pub replay: Option<Replay>,
+    /// A past run of the current test's text, following the current test at the same time into
+    /// it.
+    pub ghost: Option<Replay>,
+    /// Connection to the race being played, see [`Model::join_race`].
+    pub race: Option<RaceClient>,
+    /// Per-grapheme statistics over the whole history, kept up to date as tests finish.
+    pub key_statistics: std::collections::BTreeMap<String, KeyStatistics>,
+    pub transition_statistics: std::collections::BTreeMap<String, KeyStatistics>,
+    /// Word weights used by adaptive sampling, recomputed whenever a test finishes.
+    pub practice_weights: PracticeWeights,
+    pub heatmap_metric: HeatmapMetric,
+    pub status_message: Option<String>,
+    /// Generates the seed of every new test.
+    pub rng: rand::rngs::StdRng,
+    /// Samples the target text of the current test, seeded with the test's seed.
+    pub test_rng: rand::rngs::StdRng,
+    /// Where the target text of the current test stops in its corpus, so that timed tests grow
+    /// from there, see [`Corpus::sample`].
+    pub sample_position: Option<usize>,
 }
 
 impl Model {
-    pub fn update(&mut self, action: Action) {
+    pub fn new(config: Config, corpora: Vec<Corpus>, themes: Vec<Theme>, history: History) -> Self {
+        let rng = match config.seed {
+            Some(seed) => rand::rngs::StdRng::seed_from_u64(seed),
+            None => rand::rngs::StdRng::from_entropy(),
+        };
+
+        let key_statistics = history.key_statistics();
+        let transition_statistics = history.transition_statistics();
+        let practice_weights = PracticeWeights::new(&key_statistics, &transition_statistics);
+
+        let mut model = Model {
+            frame_statistics: FrameStatistics::default(),
+            config,
+            should_quit: false,
+            needs_redraw: true,
+            next_timer: None,
+            displayed_caret: 0,
+            caret_moved_at: clock::now(),
+            current_test: Test::new(""),
+            test_corpus: String::new(),
+            test_length: 0,
+            corpora,
+            themes,
+            screen: Screen::Test,
+            history,
+            history_browser: HistoryBrowser::default(),
+            results_tab: ResultsTab::Summary,
+            previous_personal_best: None,
+            results: TestResults::default(),
+            replay: None,
+            ghost: None,
+            race: None,
+            key_statistics,
+            transition_statistics,
+            practice_weights,
+            heatmap_metric: HeatmapMetric::ErrorRate,
+            status_message: None,
+            rng,
+            test_rng: rand::rngs::StdRng::seed_from_u64(0),
+            sample_position: None,
+        };
+        model.next_test();
+        model
+    }
+
+    pub fn update(&mut self, TimedAction { action, time }: TimedAction) {
+        if !self.allows(action) {
+            return;
+        }
+        self.needs_redraw = true;
+
         match action {
-            Action::CharacterInput(c) => self.current_test.input(c),
-            Action::DeleteCharacter => self.current_test.delete_character(),
-            Action::DeleteWord => {
-                self.current_test.delete_word();
+            Action::CharacterInput(c) => {
+                let was_finished = self.current_test.is_finished();
+                self.current_test.input(c, time);
+                if !was_finished && self.current_test.is_finished() {
+                    self.finish_test();
+                } else {
+                    self.extend_timed_test();
+                }
+            }
+            Action::DeleteCharacter => self.current_test.delete_character(time),
+            Action::DeleteWord => self.current_test.delete_word(time),
+            Action::Restart => {
+                self.current_test.restart();
+                self.displayed_caret = 0;
+                self.screen = Screen::Test;
+                // The test just finished may be the new best.
+                self.ghost = self.find_ghost();
             }
-            Action::Restart => self.current_test.restart(),
             Action::NextTest => self.next_test(),
-            Action::NextCorpus => {
-                self.config.corpus = match self.config.corpus.id {
-                    EmbeddedCorpora::English200Words => {
-                        Corpus::embedded(EmbeddedCorpora::English200Bigrams)
-                    }
-                    EmbeddedCorpora::English200Bigrams => {
-                        Corpus::embedded(EmbeddedCorpora::English200Trigrams)
-                    }
-                    EmbeddedCorpora::English200Trigrams => {
-                        Corpus::embedded(EmbeddedCorpora::English200Tetragrams)
-                    }
-                    EmbeddedCorpora::English200Tetragrams => {
-                        Corpus::embedded(EmbeddedCorpora::English200Words)
+            Action::NextCorpus => self.cycle_corpus(true),
+            Action::PreviousCorpus => self.cycle_corpus(false),
+            Action::IncreaseTestLength => match self.config.test_mode {
+                TestMode::Words => {
+                    let new_length = std::cmp::min(
+                        self.config.test_length + 1,
+                        self.config.corpus.max_test_length(),
+                    );
+                    if new_length != self.config.test_length {
+                        self.config.test_length = new_length;
+                        self.next_test();
                     }
-                };
-                self.config.test_length =
-                    std::cmp::min(self.config.test_length, self.config.corpus.words.len());
-                self.next_test()
-            }
-            Action::PreviousCorpus => {
-                self.config.corpus = match self.config.corpus.id {
-                    EmbeddedCorpora::English200Words => {
-                        Corpus::embedded(EmbeddedCorpora::English200Tetragrams)
-                    }
-                    EmbeddedCorpora::English200Bigrams => {
-                        Corpus::embedded(EmbeddedCorpora::English200Words)
+                }
+                TestMode::Time => {
+                    if let Some(&time_limit) = TIME_LIMITS
+                        .iter()
+                        .find(|&&time_limit| time_limit > self.config.time_limit)
+                    {
+                        self.config.time_limit = time_limit;
+                        self.next_test();
                     }
-                    EmbeddedCorpora::English200Trigrams => {
-                        Corpus::embedded(EmbeddedCorpora::English200Bigrams)
+                }
+            },
+            Action::DecreaseTestLength => match self.config.test_mode {
+                TestMode::Words => {
+                    if self.config.test_length > 1 {
+                        self.config.test_length -= 1;
+                        self.next_test();
                     }
-                    EmbeddedCorpora::English200Tetragrams => {
-                        Corpus::embedded(EmbeddedCorpora::English200Trigrams)
+                }
+                TestMode::Time => {
+                    if let Some(&time_limit) = TIME_LIMITS
+                        .iter()
+                        .rev()
+                        .find(|&&time_limit| time_limit < self.config.time_limit)
+                    {
+                        self.config.time_limit = time_limit;
+                        self.next_test();
                     }
-                };
-                self.config.test_length =
-                    std::cmp::min(self.config.test_length, self.config.corpus.words.len());
-                self.next_test()
-            }
-            Action::IncreaseTestLength => {
-                let new_length =
-                    std::cmp::min(self.config.test_length + 1, self.config.corpus.words.len());
-                if new_length != self.config.test_length {
-                    self.config.test_length = new_length;
-                    self.next_test();
                 }
+            },
+            Action::NextSamplingMethod => {
+                self.config.sampling_method = match self.config.sampling_method {
+                    SamplingMethod::Top => SamplingMethod::Random,
+                    SamplingMethod::Random => SamplingMethod::Adaptive,
+                    SamplingMethod::Adaptive => SamplingMethod::Top,
+                };
+                self.next_test();
             }
-            Action::DecreaseTestLength => {
-                if self.config.test_length > 1 {
-                    self.config.test_length -= 1;
-                    self.next_test();
+            Action::NextTheme => {
+                if !self.themes.is_empty() {
+                    let index = self
+                        .themes
+                        .iter()
+                        .position(|theme| theme.name == self.config.theme.name)
+                        .map_or(0, |index| (index + 1) % self.themes.len());
+                    self.config.theme = self.themes[index].clone();
                 }
             }
+            Action::ToggleGhost => {
+                self.config.ghost = !self.config.ghost;
+                self.ghost = self.find_ghost();
+            }
+            Action::ToggleWordByWord => {
+                self.config.word_by_word = !self.config.word_by_word;
+                self.current_test.word_by_word =
+                    self.config.word_by_word && self.test_corpus().kind != CorpusKind::Code;
+                self.current_test.restart();
+                self.screen = Screen::Test;
+            }
+            Action::NextStrictness => {
+                self.config.strictness = self.config.strictness.next();
+                self.current_test.strictness = self.config.strictness;
+                self.current_test.restart();
+                self.screen = Screen::Test;
+            }
+            Action::ToggleTestMode => {
+                self.config.test_mode = match self.config.test_mode {
+                    TestMode::Words => TestMode::Time,
+                    TestMode::Time => TestMode::Words,
+                };
+                self.next_test();
+            }
             Action::ToggleFrameStatistics => {
                 self.config.show_frame_statistics = !self.config.show_frame_statistics
             }
             Action::ToggleLiveTypingStatistics => {
                 self.config.show_live_typing_statistics = !self.config.show_live_typing_statistics
             }
+            Action::ToggleHistory => {
+                self.screen = match self.screen {
+                    Screen::History => self.history_browser.return_screen,
+                    screen => {
+                        self.history_browser.selected = 0;
+                        self.history_browser.return_screen = screen;
+                        Screen::History
+                    }
+                }
+            }
+            Action::ToggleHeatmap => {
+                self.screen = match self.screen {
+                    Screen::Heatmap => Screen::Test,
+                    _ => Screen::Heatmap,
+                }
+            }
+            Action::NextKeyboardLayout => {
+                self.config.keyboard_layout = self.config.keyboard_layout.next()
+            }
+            Action::PreviousKeyboardLayout => {
+                self.config.keyboard_layout = self.config.keyboard_layout.previous()
+            }
+            Action::NextHeatmapMetric => {
+                self.heatmap_metric = match self.heatmap_metric {
+                    HeatmapMetric::ErrorRate => HeatmapMetric::Latency,
+                    HeatmapMetric::Latency => HeatmapMetric::ErrorRate,
+                }
+            }
+            Action::NextHistorySort => {
+                self.history_browser.sort = self.history_browser.sort.next();
+                self.history_browser.selected = 0;
+            }
+            Action::ReverseHistorySort => {
+                self.history_browser.descending = !self.history_browser.descending;
+                self.history_browser.selected = 0;
+            }
+            Action::NextHistoryCorpus => self
+                .history_browser
+                .cycle_corpus_filter(&self.history, true),
+            Action::PreviousHistoryCorpus => self
+                .history_browser
+                .cycle_corpus_filter(&self.history, false),
+            Action::SelectPreviousHistoryEntry => {
+                self.history_browser.selected = self.history_browser.selected.saturating_sub(1)
+            }
+            Action::SelectNextHistoryEntry => {
+                let row_count = self.history_browser.rows(&self.history).len();
+                self.history_browser.selected = std::cmp::min(
+                    self.history_browser.selected + 1,
+                    row_count.saturating_sub(1),
+                );
+            }
+            Action::NextResultsTab => {
+                let index = ResultsTab::ALL
+                    .iter()
+                    .position(|&tab| tab == self.results_tab)
+                    .unwrap_or(0);
+                self.results_tab = ResultsTab::ALL[(index + 1) % ResultsTab::ALL.len()];
+            }
+            Action::PreviousResultsTab => {
+                let index = ResultsTab::ALL
+                    .iter()
+                    .position(|&tab| tab == self.results_tab)
+                    .unwrap_or(0);
+                self.results_tab =
+                    ResultsTab::ALL[(index + ResultsTab::ALL.len() - 1) % ResultsTab::ALL.len()];
+            }
+            Action::DismissResults => self.screen = Screen::Test,
+            Action::ReplayTest => self.start_replay(),
+            Action::RaceTest => self.race_past_run(),
+            Action::ExportResults => self.export_results(),
+            Action::StartRace => self.start_race(),
+            Action::ToggleReplayPause => self.update_replay(time, Replay::toggle_pause),
+            Action::StepReplayForward => self.update_replay(time, Replay::step_forward),
+            Action::StepReplayBackward => self.update_replay(time, Replay::step_backward),
+            Action::IncreaseReplaySpeed => {
+                self.update_replay(time, |replay, _| replay.increase_speed())
+            }
+            Action::DecreaseReplaySpeed => {
+                self.update_replay(time, |replay, _| replay.decrease_speed())
+            }
+            Action::RestartReplay => self.update_replay(time, Replay::restart),
+            Action::CloseReplay => {
+                if let Some(replay) = self.replay.take() {
+                    self.screen = replay.return_screen;
+                }
+            }
+            Action::Resize => {}
             Action::Quit => self.should_quit = true,
         }
+
+        self.report_race_progress();
     }
 
-    fn next_test(&mut self) {
-        self.current_test = Test::new(
-            self.config
-                .corpus
-                .sample(self.config.test_length, SamplingMethod::Random)
-                .to_string()
-                .as_str(),
+    /// Whether `action` can be taken now. Races keep every player on the same test, so only
+    /// typing and display settings are available while in one.
+    pub fn allows(&self, action: Action) -> bool {
+        let Some(race) = &self.race else {
+            return true;
+        };
+        match action {
+            // Starting a race while others are still typing would cut their race short.
+            Action::StartRace => {
+                race.is_host() && race.is_connected && (!race.is_racing || race.is_race_over())
+            }
+            Action::CharacterInput(_)
+            | Action::DeleteCharacter
+            | Action::DeleteWord
+            | Action::NextTheme
+            | Action::ToggleFrameStatistics
+            | Action::ToggleLiveTypingStatistics
+            | Action::Resize
+            | Action::Quit => true,
+            _ => false,
+        }
+    }
+
+    /// Advances time-dependent state, such as the countdown of timed tests, and requests a redraw
+    /// when a timer has fired. Should be called before every frame and whenever
+    /// [`Model::time_until_next_timer`] has elapsed.
+    pub fn tick(&mut self) {
+        let now = clock::now();
+
+        let was_finished = self.current_test.is_finished();
+        self.current_test.check_time_limit(now);
+        if !was_finished && self.current_test.is_finished() {
+            self.finish_test();
+            self.needs_redraw = true;
+        }
+
+        if let Some(replay) = &mut self.replay {
+            replay.advance(now);
+        }
+
+        let race_events = self.race.as_mut().map(RaceClient::poll).unwrap_or_default();
+        for event in race_events {
+            self.needs_redraw = true;
+            match event {
+                RaceEvent::Started(settings) => self.start_race_test(&settings),
+                RaceEvent::Updated => {}
+                RaceEvent::Disconnected => {
+                    self.status_message = Some("Lost the connection to the race".to_string())
+                }
+            }
+        }
+        self.report_race_progress();
+
+        if let Some(ghost) = &mut self.ghost {
+            if self.current_test.is_started() {
+                ghost.seek(self.current_test.duration_at(now), now);
+            } else if ghost.played > 0 {
+                ghost.rewind();
+            }
+        }
+
+        if self.next_timer.is_some_and(|next_timer| now >= next_timer) {
+            self.needs_redraw = true;
+        }
+
+        let caret = self.current_test.caret_index();
+        if self.config.smooth_caret {
+            if self.displayed_caret != caret
+                && now.saturating_duration_since(self.caret_moved_at) >= CARET_ANIMATION_INTERVAL
+            {
+                // Long jumps, such as deleting a word, are covered in a few frames.
+                let distance = self.displayed_caret.abs_diff(caret);
+                let step = std::cmp::max(1, distance / 3);
+                self.displayed_caret = if self.displayed_caret < caret {
+                    self.displayed_caret + step
+                } else {
+                    self.displayed_caret - step
+                };
+                self.caret_moved_at = now;
+                self.needs_redraw = true;
+            }
+        } else {
+            self.displayed_caret = caret;
+        }
+
+        self.next_timer = self.schedule_next_timer(now, caret);
+    }
+
+    /// How long the main loop may wait for input before the view goes stale, `None` if it can
+    /// wait indefinitely.
+    pub fn time_until_next_timer(&self) -> Option<std::time::Duration> {
+        let timer = self
+            .next_timer
+            .map(|next_timer| next_timer.saturating_duration_since(clock::now()));
+        // News from the other players of a race is checked for without redrawing.
+        let race_poll = self
+            .race
+            .as_ref()
+            .filter(|race| race.is_connected)
+            .map(|_| RACE_POLL_INTERVAL);
+
+        [timer, race_poll].into_iter().flatten().min()
+    }
+
+    /// While a test runs, its clock, countdown and live statistics change every second. A smooth
+    /// caret moves a step every [`CARET_ANIMATION_INTERVAL`] until it reaches `caret`. A playing
+    /// replay has timers of its own, see [`Replay::next_timer`], and a ghost moves at the time of
+    /// each of its inputs.
+    fn schedule_next_timer(
+        &self,
+        now: std::time::Instant,
+        caret: usize,
+    ) -> Option<std::time::Instant> {
+        let clock_timer = match self.current_test.start_time {
+            Some(start_time) if !self.current_test.is_finished() => {
+                let elapsed_seconds = now.saturating_duration_since(start_time).as_secs();
+                Some(start_time + std::time::Duration::from_secs(elapsed_seconds + 1))
+            }
+            _ => None,
+        };
+        let caret_timer = (self.displayed_caret != caret)
+            .then_some(self.caret_moved_at + CARET_ANIMATION_INTERVAL);
+
+        let replay_timer = self.replay.as_ref().and_then(Replay::next_timer);
+        let ghost_timer = match (&self.ghost, self.current_test.start_time) {
+            (Some(ghost), Some(start_time)) if !self.current_test.is_finished() => ghost
+                .next_input_time()
+                .map(|input_time| start_time + input_time),
+            _ => None,
+        };
+
+        [clock_timer, caret_timer, replay_timer, ghost_timer]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    /// Plays back the test just finished on the results screen, or the test selected on the
+    /// history screen.
+    fn start_replay(&mut self) {
+        let record = match self.screen {
+            Screen::Results => self.history.records.last(),
+            Screen::History => self
+                .history_browser
+                .rows(&self.history)
+                .get(self.history_browser.selected)
+                .copied(),
+            _ => None,
+        };
+        let Some(record) = record else {
+            return;
+        };
+
+        match Replay::new(record, self.screen, clock::now()) {
+            Some(replay) => {
+                self.replay = Some(replay);
+                self.screen = Screen::Replay;
+                self.status_message = None;
+            }
+            None => {
+                self.status_message =
+                    Some("This test was recorded without its keystrokes".to_string())
+            }
+        }
+    }
+
+    /// Writes the finished test, or the tests listed on the history screen, with their keystrokes
+    /// to the export directory in every format.
+    fn export_results(&mut self) {
+        let records = match self.screen {
+            Screen::Results => self.history.records.last().into_iter().collect(),
+            Screen::History => self.history_browser.rows(&self.history),
+            _ => Vec::new(),
+        };
+        if records.is_empty() {
+            self.status_message = Some("No tests to export".to_string());
+            return;
+        }
+
+        let Some(directory) = self
+            .config
+            .export_directory
+            .clone()
+            .or_else(|| export::default_directory(&self.history))
+        else {
+            self.status_message =
+                Some("Set export_directory in the configuration to export tests".to_string());
+            return;
+        };
+        let stem = export::unused_stem(&directory, std::time::SystemTime::now());
+
+        self.status_message = Some(match Export::new(&records, true).write_files(&stem) {
+            Ok(()) => format!(
+                "Exported {} {} to {}.{{json,csv}}",
+                records.len(),
+                if records.len() == 1 { "test" } else { "tests" },
+                stem.display()
+            ),
+            Err(error) => format!("Could not export tests: {}", error),
+        });
+    }
+
+    /// Takes part in the race of `client`, starting in its lobby.
+    pub fn join_race(&mut self, client: RaceClient) {
+        self.race = Some(client);
+        self.ghost = None;
+        self.screen = Screen::Race;
+    }
+
+    /// Picks the text of the next race and has the server start it for everyone, if hosting.
+    fn start_race(&mut self) {
+        if self.race.is_none() {
+            return;
+        }
+
+        self.next_test();
+        if let Some(time_limit) = self.current_test.time_limit {
+            // Every player has to type the same text, so timed races get enough of it up front
+            // for the fastest typists instead of growing as they type.
+            let length = time_limit.as_secs() as usize * RACE_GRAPHEMES_PER_SECOND;
+            while self.current_test.target_text_grapheme_count < length {
+                let text = self.sample_text(TIMED_TEST_CHUNK_LENGTH);
+                let separator = self.test_corpus().separator().to_string();
+                self.current_test.extend_target(&text, &separator);
+            }
+        }
+        let settings = RaceSettings {
+            corpus: self.test_corpus.clone(),
+            test_length: self.test_length,
+            target_text: self.current_test.target_text.clone(),
+            time_limit: self.current_test.time_limit,
+            word_by_word: self.current_test.word_by_word,
+            strictness: self.current_test.strictness,
+        };
+
+        // The race starts once the server sends it back.
+        self.screen = Screen::Race;
+        if let Some(race) = &mut self.race {
+            if let Err(error) = race.send(&Message::Start(settings)) {
+                self.status_message = Some(format!("Could not start the race: {}", error));
+            }
+        }
+    }
+
+    fn start_race_test(&mut self, settings: &RaceSettings) {
+        self.start_test_with_text(
+            &settings.corpus,
+            settings.test_length,
+            &settings.target_text,
+            settings.time_limit,
+            settings.word_by_word,
+            settings.strictness,
         );
+        self.ghost = None;
+    }
+
+    fn report_race_progress(&mut self) {
+        if let Some(race) = &mut self.race {
+            if let Err(error) = race.report(&self.current_test) {
+                self.status_message = Some(format!("Lost the connection to the race: {}", error));
+            }
+        }
+    }
+
+    /// Starts a test with the text of the test selected on the history screen, racing that run as
+    /// a ghost.
+    fn race_past_run(&mut self) {
+        let Some(record) = self
+            .history_browser
+            .rows(&self.history)
+            .get(self.history_browser.selected)
+            .copied()
+            .cloned()
+        else {
+            return;
+        };
+
+        if record.inputs.is_empty() {
+            self.status_message = Some("This test was recorded without its keystrokes".to_string());
+            return;
+        }
+
+        self.start_test_with_text(
+            &record.corpus,
+            record.test_length,
+            &record.target_text,
+            record.time_limit,
+            record.word_by_word,
+            record.strictness,
+        );
+        self.ghost = Replay::new(&record, Screen::Test, clock::now());
+    }
+
+    /// Starts a test of `target_text` rather than one sampled from the corpus, recorded as taken
+    /// from `corpus` with `test_length` words. The configuration is left alone, the next test
+    /// goes back to it. The test has no seed to share it by, since how it was sampled is not
+    /// known.
+    fn start_test_with_text(
+        &mut self,
+        corpus: &str,
+        test_length: usize,
+        target_text: &str,
+        time_limit: Option<std::time::Duration>,
+        word_by_word: bool,
+        strictness: Strictness,
+    ) {
+        self.test_corpus = corpus.to_string();
+        self.test_length = test_length;
+        self.sample_position = None;
+        self.screen = Screen::Test;
+        self.displayed_caret = 0;
+        self.status_message = None;
+        self.current_test = match time_limit {
+            Some(time_limit) => Test::timed(target_text, time_limit),
+            None => Test::new(target_text),
+        };
+        self.current_test.word_by_word = word_by_word;
+        self.current_test.strictness = strictness;
+    }
+
+    /// The fastest past run of the current test's text that can be raced, if ghosts are enabled.
+    /// Timed tests grow as they are typed, so their runs only have to start with the same text.
+    fn find_ghost(&self) -> Option<Replay> {
+        if !self.config.ghost {
+            return None;
+        }
+
+        let test = &self.current_test;
+        let record = self
+            .history
+            .records
+            .iter()
+            .filter(|record| {
+                !record.inputs.is_empty()
+                    && record.time_limit == test.time_limit
+                    && match test.time_limit {
+                        Some(_) => record.target_text.starts_with(&test.target_text),
+                        None => record.target_text == test.target_text,
+                    }
+            })
+            .max_by(|a, b| a.wpm.total_cmp(&b.wpm))?;
+
+        Replay::new(record, Screen::Test, clock::now())
+    }
+
+    /// Brings the replay up to `time` before changing it with `change`.
+    fn update_replay(
+        &mut self,
+        time: std::time::Instant,
+        change: fn(&mut Replay, std::time::Instant),
+    ) {
+        if let Some(replay) = &mut self.replay {
+            replay.advance(time);
+            change(replay, time);
+        }
+    }
+
+    fn cycle_corpus(&mut self, forward: bool) {
+        if self.corpora.is_empty() {
+            return;
+        }
+
+        let count = self.corpora.len();
+        let index = self
+            .corpora
+            .iter()
+            .position(|corpus| corpus.id == self.config.corpus.id);
+        let index = match (index, forward) {
+            (Some(index), true) => (index + 1) % count,
+            (Some(index), false) => (index + count - 1) % count,
+            (None, _) => 0,
+        };
+
+        self.config.corpus = self.corpora[index].clone();
+        self.config.test_length = std::cmp::min(
+            self.config.test_length,
+            self.config.corpus.max_test_length(),
+        );
+        self.next_test()
+    }
+
+    fn finish_test(&mut self) {
+        self.previous_personal_best = self.history.personal_best(
+            &self.test_corpus,
+            self.test_length,
+            self.current_test.time_limit,
+        );
+        self.record_test();
+        self.results = TestResults::new(&self.current_test);
+        self.results_tab = ResultsTab::Summary;
+        // Racers watch the others finish instead.
+        self.screen = match self.race {
+            Some(_) => Screen::Race,
+            None => Screen::Results,
+        };
+    }
+
+    fn record_test(&mut self) {
+        let record = TestRecord::new(&self.current_test, &self.test_corpus, self.test_length);
+        statistics::merge_statistics(&mut self.key_statistics, &record.key_statistics);
+        statistics::merge_statistics(
+            &mut self.transition_statistics,
+            &record.transition_statistics,
+        );
+        self.practice_weights =
+            PracticeWeights::new(&self.key_statistics, &self.transition_statistics);
+        self.status_message = match self.history.record(record) {
+            Ok(()) => None,
+            Err(error) => Some(format!("Could not save test to history: {}", error)),
+        };
+    }
+
+    fn next_test(&mut self) {
+        // 32 bits are plenty of distinct tests and keep test codes short.
+        let seed = u64::from(rand::Rng::gen::<u32>(&mut self.rng));
+        self.start_test(seed);
+    }
+
+    fn start_test(&mut self, seed: u64) {
+        self.screen = Screen::Test;
+        self.displayed_caret = 0;
+        self.test_rng = rand::rngs::StdRng::seed_from_u64(seed);
+        self.sample_position = None;
+        self.test_corpus = self.config.corpus.name.clone();
+        self.test_length = self.config.test_length;
+        self.current_test = match self.config.test_mode {
+            TestMode::Words => Test::new(&self.sample_text(self.config.test_length)),
+            TestMode::Time => Test::timed(
+                &self.sample_text(TIMED_TEST_CHUNK_LENGTH),
+                self.config.time_limit,
+            ),
+        };
+        self.current_test.seed = Some(seed);
+        // Indentation makes no sense split into words.
+        self.current_test.word_by_word =
+            self.config.word_by_word && self.config.corpus.kind != CorpusKind::Code;
+        self.current_test.strictness = self.config.strictness;
+        self.ghost = self.find_ghost();
+    }
+
+    /// Code to share the current test with, or `None` if its text cannot be reproduced.
+    pub fn test_code(&self) -> Option<TestCode> {
+        if self.config.sampling_method == SamplingMethod::Adaptive {
+            return None;
+        }
+
+        Some(TestCode {
+            corpus: self.config.corpus.code_name(),
+            test_mode: self.config.test_mode,
+            test_length: self.config.test_length,
+            time_limit: self.config.time_limit,
+            sampling_method: self.config.sampling_method,
+            seed: self.current_test.seed?,
+        })
+    }
+
+    /// Switches to the settings encoded in `code` and starts the exact test it describes.
+    pub fn start_test_from_code(&mut self, code: &TestCode) -> std::io::Result<()> {
+        let corpus = self
+            .corpora
+            .iter()
+            .find(|corpus| corpus.code_name() == code.corpus)
+            .cloned()
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("unknown corpus '{}' in test code", code.corpus),
+                )
+            })?;
+
+        if code.test_mode == TestMode::Words && code.test_length > corpus.max_test_length() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "test code asks for {} words but corpus '{}' only has {}",
+                    code.test_length,
+                    corpus.name,
+                    corpus.max_test_length()
+                ),
+            ));
+        }
+
+        self.config.corpus = corpus;
+        self.config.test_mode = code.test_mode;
+        match code.test_mode {
+            TestMode::Words => self.config.test_length = code.test_length,
+            TestMode::Time => self.config.time_limit = code.time_limit,
+        }
+        self.config.sampling_method = code.sampling_method;
+        self.start_test(code.seed);
+        Ok(())
+    }
+
+    /// Timed tests must never run out of text, so more words are appended from the corpus as the
+    /// typist approaches the end of the target.
+    fn extend_timed_test(&mut self) {
+        // Races get all of their text up front in `start_race`, so that everyone types the same.
+        if self.current_test.time_limit.is_none() || self.race.is_some() {
+            return;
+        }
+
+        let remaining_graphemes = self
+            .current_test
+            .target_text_grapheme_count
+            .saturating_sub(self.current_test.target_position());
+        if remaining_graphemes < TIMED_TEST_LOOKAHEAD {
+            let text = self.sample_text(TIMED_TEST_CHUNK_LENGTH);
+            let separator = self.test_corpus().separator().to_string();
+            self.current_test.extend_target(&text, &separator);
+        }
+    }
+
+    /// The corpus the current test was taken from, or the configured one if it is not known.
+    pub fn test_corpus(&self) -> &Corpus {
+        Self::find_corpus(&self.config, &self.corpora, &self.test_corpus)
+    }
+
+    fn find_corpus<'a>(config: &'a Config, corpora: &'a [Corpus], name: &str) -> &'a Corpus {
+        if config.corpus.name == name {
+            return &config.corpus;
+        }
+        corpora
+            .iter()
+            .find(|corpus| corpus.name == name)
+            .unwrap_or(&config.corpus)
+    }
+
+    fn sample_text(&mut self, length: usize) -> String {
+        let practice_weights = &self.practice_weights;
+        Self::find_corpus(&self.config, &self.corpora, &self.test_corpus).sample(
+            length,
+            self.config.sampling_method,
+            &mut self.test_rng,
+            |word| practice_weights.word_weight(word),
+            &mut self.sample_position,
+        )
     }
 }
 
 impl Default for Model {
     fn default() -> Self {
-        let mut model = Model {
-            frame_statistics: FrameStatistics::default(),
-            config: Config::default(),
-            should_quit: false,
-            current_test: Test::new(""),
-        };
-        model.next_test();
-        model
+        Model::new(
+            Config::default(),
+            Corpus::all_embedded(),
+            Theme::all_builtin(),
+            History::in_memory(),
+        )
     }
 }
 
+/// Time limits, in order, that the test duration steps through in timed mode.
+pub const TIME_LIMITS: [std::time::Duration; 4] = [
+    std::time::Duration::from_secs(15),
+    std::time::Duration::from_secs(30),
+    std::time::Duration::from_secs(60),
+    std::time::Duration::from_secs(120),
+];
+
+/// Time between two checks for news from the other players of a race.
+const RACE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// Typing speed timed races have enough text for, 20 graphemes a second being 240 WPM.
+const RACE_GRAPHEMES_PER_SECOND: usize = 20;
+
+/// Time between two steps of a smooth caret.
+const CARET_ANIMATION_INTERVAL: std::time::Duration = std::time::Duration::from_millis(15);
+
+const TIMED_TEST_CHUNK_LENGTH: usize = 50;
+const TIMED_TEST_LOOKAHEAD: usize = 100;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestMode {
+    /// The test ends once the sampled words have been typed.
+    Words,
+    /// The test ends once the time limit runs out.
+    Time,
+}
+
+/// How mistakes have to be dealt with before a test can go on.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strictness {
+    /// Mistakes can be left in the text.
+    #[default]
+    Lenient,
+    /// A wrong grapheme is not entered, typing continues once the right one is pressed.
+    StopOnError,
+    /// The test only finishes once the typed text matches the target exactly.
+    MustCorrect,
+}
+
+impl Strictness {
+    pub const ALL: [Strictness; 3] = [
+        Strictness::Lenient,
+        Strictness::StopOnError,
+        Strictness::MustCorrect,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Strictness::Lenient => "Lenient",
+            Strictness::StopOnError => "Stop on error",
+            Strictness::MustCorrect => "Must correct",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = Strictness::ALL
+            .iter()
+            .position(|&strictness| strictness == self)
+            .unwrap_or(0);
+        Strictness::ALL[(index + 1) % Strictness::ALL.len()]
+    }
+}
+
+/// Shape of the terminal cursor, which marks the caret while typing.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaretStyle {
+    Block,
+    Underline,
+    Bar,
+}
+
 pub struct Config {
     pub show_frame_statistics: bool,
     pub show_live_typing_statistics: bool,
     pub corpus: Corpus,
     pub test_length: usize,
+    pub test_mode: TestMode,
+    pub time_limit: std::time::Duration,
+    pub sampling_method: SamplingMethod,
+    pub keyboard_layout: KeyboardLayout,
+    /// See [`Test::word_by_word`].
+    pub word_by_word: bool,
+    pub strictness: Strictness,
+    pub caret_style: CaretStyle,
+    /// Let the terminal blink the caret.
+    pub caret_blink: bool,
+    /// Slide the caret to its new position instead of jumping there.
+    pub smooth_caret: bool,
+    /// Race the best past run of the same text, see [`Model::ghost`].
+    pub ghost: bool,
+    /// Where exports made from the results and history screens go, see
+    /// [`export::default_directory`] when not set.
+    pub export_directory: Option<std::path::PathBuf>,
+    pub theme: Theme,
+    pub keymap: Keymap,
+    pub seed: Option<u64>,
 }
 
 impl Default for Config {
@@ -120,14 +1020,31 @@ impl Default for Config {
             show_live_typing_statistics: true,
             corpus: Corpus::embedded(EmbeddedCorpora::English200Words),
             test_length: 50,
+            test_mode: TestMode::Words,
+            time_limit: std::time::Duration::from_secs(30),
+            sampling_method: SamplingMethod::Random,
+            keyboard_layout: KeyboardLayout::Qwerty,
+            word_by_word: false,
+            strictness: Strictness::Lenient,
+            caret_style: CaretStyle::Block,
+            caret_blink: false,
+            smooth_caret: false,
+            ghost: false,
+            export_directory: None,
+            theme: Theme::default(),
+            keymap: Keymap::default(),
+            seed: None,
         }
     }
 }
 
 pub struct FrameStatistics {
     pub frame_begin: std::time::Instant,
+    /// Time between the beginnings of the last two frames.
     pub last_frame_duration: std::time::Duration,
     pub average_frame_duration: std::time::Duration,
+    /// Time spent drawing a frame.
+    pub average_render_duration: std::time::Duration,
 }
 
 impl FrameStatistics {
@@ -138,6 +1055,12 @@ impl FrameStatistics {
         self.average_frame_duration =
             self.average_frame_duration.mul_f64(0.8) + self.last_frame_duration.mul_f64(0.2);
     }
+
+    pub fn end_frame(&mut self) {
+        let render_duration = self.frame_begin.elapsed();
+        self.average_render_duration =
+            self.average_render_duration.mul_f64(0.8) + render_duration.mul_f64(0.2);
+    }
 }
 
 impl Default for FrameStatistics {
@@ -146,10 +1069,64 @@ impl Default for FrameStatistics {
             frame_begin: std::time::Instant::now(),
             last_frame_duration: std::time::Duration::default(),
             average_frame_duration: std::time::Duration::default(),
+            average_render_duration: std::time::Duration::default(),
+        }
+    }
+}
+
+pub enum KeystrokeKind {
+    Input {
+        position: usize,
+        typed: String,
+        expected: Option<String>,
+        is_correction: bool,
+    },
+    DeleteCharacter {
+        span: std::ops::Range<usize>,
+    },
+    DeleteWord {
+        span: std::ops::Range<usize>,
+    },
+}
+
+pub struct Keystroke {
+    pub time: std::time::Duration,
+    pub kind: KeystrokeKind,
+}
+
+/// What the typist asked the test to do, before the test decides what it means.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Input {
+    Character(char),
+    DeleteCharacter,
+    DeleteWord,
+}
+
+/// An [`Input`] and how long into the test it was made, enough to play a test back. Stored as a
+/// `[milliseconds, input]` pair to keep history records short.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(from = "(u64, Input)", into = "(u64, Input)")]
+pub struct TimedInput {
+    pub time: std::time::Duration,
+    pub input: Input,
+}
+
+impl From<(u64, Input)> for TimedInput {
+    fn from((milliseconds, input): (u64, Input)) -> Self {
+        TimedInput {
+            time: std::time::Duration::from_millis(milliseconds),
+            input,
         }
     }
 }
 
+impl From<TimedInput> for (u64, Input) {
+    fn from(TimedInput { time, input }: TimedInput) -> Self {
+        (time.as_millis() as u64, input)
+    }
+}
+
 pub struct Test {
     pub target_text: String,
     pub target_text_grapheme_count: usize,
@@ -157,6 +1134,45 @@ pub struct Test {
     pub current_text_grapheme_count: usize,
     pub start_time: Option<std::time::Instant>,
     pub end_time: Option<std::time::Instant>,
+    pub time_limit: Option<std::time::Duration>,
+    /// Seed the target text was sampled with, if it was sampled from a corpus.
+    pub seed: Option<u64>,
+    /// Compare the typed text with the target one word at a time, see [`Test::words`].
+    pub word_by_word: bool,
+    pub strictness: Strictness,
+    pub keystrokes: Vec<Keystroke>,
+    /// Every input of the test, including the ones that changed nothing, see [`TimedInput`].
+    pub inputs: Vec<TimedInput>,
+    /// Positions in the typed text of the indentation typed by [`Test::auto_indent`], which the
+    /// statistics leave out as the typist did not type it.
+    auto_indented: std::collections::BTreeSet<usize>,
+    /// Positions in the typed text that were ever deleted, which makes typing there again a
+    /// correction.
+    deleted_positions: std::collections::HashSet<usize>,
+    /// Bumped whenever the typed or the target text changes.
+    revision: u64,
+    /// Last result of [`Test::alignment`], with the state of the test it was computed for.
+    alignment: std::cell::RefCell<Option<(AlignmentKey, Alignment)>>,
+    /// Last result of [`Test::token_kinds`], with the target and language it was computed for.
+    token_kinds: std::cell::RefCell<Option<(HighlightKey, Vec<TokenKind>)>>,
+}
+
+/// What [`Test::alignment`] depends on: the revision of the texts, whether the test is finished,
+/// and whether it is typed word by word.
+type AlignmentKey = (u64, bool, bool);
+
+/// What [`Test::token_kinds`] depends on: the length of the target text, which only grows, and
+/// the language it is highlighted as.
+type HighlightKey = (usize, Option<String>);
+
+/// One word of the target and what was typed for it, in word-by-word mode.
+pub struct WordProgress<'a> {
+    /// Grapheme index of the first grapheme of the word in the target text.
+    pub start: usize,
+    pub target: Vec<&'a str>,
+    pub typed: Vec<&'a str>,
+    /// Whether the typist has moved on to the next word with space.
+    pub is_submitted: bool,
 }
 
 impl Test {
@@ -171,19 +1187,37 @@ impl Test {
             current_text_grapheme_count: 0,
             start_time: None,
             end_time: None,
+            time_limit: None,
+            seed: None,
+            word_by_word: false,
+            strictness: Strictness::Lenient,
+            keystrokes: Vec::new(),
+            inputs: Vec::new(),
+            auto_indented: std::collections::BTreeSet::new(),
+            deleted_positions: std::collections::HashSet::new(),
+            revision: 0,
+            alignment: std::cell::RefCell::new(None),
+            token_kinds: std::cell::RefCell::new(None),
         }
     }
 
-    pub fn start(&mut self) {
-        self.start_time = Some(std::time::Instant::now());
+    pub fn timed(target_text: &str, time_limit: std::time::Duration) -> Self {
+        Test {
+            time_limit: Some(time_limit),
+            ..Test::new(target_text)
+        }
+    }
+
+    pub fn start(&mut self, time: std::time::Instant) {
+        self.start_time = Some(time);
     }
 
     pub fn is_started(&self) -> bool {
         self.start_time.is_some()
     }
 
-    pub fn finish(&mut self) {
-        self.end_time = Some(std::time::Instant::now());
+    pub fn finish(&mut self, time: std::time::Instant) {
+        self.end_time = Some(time);
     }
 
     pub fn is_finished(&self) -> bool {
@@ -191,66 +1225,366 @@ impl Test {
     }
 
     pub fn restart(&mut self) {
-        *self = Test::new(self.target_text.as_str());
+        *self = Test {
+            time_limit: self.time_limit,
+            seed: self.seed,
+            word_by_word: self.word_by_word,
+            strictness: self.strictness,
+            ..Test::new(self.target_text.as_str())
+        };
     }
 
-    pub fn input(&mut self, c: char) {
+    /// Finishes a timed test whose time limit has run out by `now`.
+    pub fn check_time_limit(&mut self, now: std::time::Instant) {
+        if let (Some(start_time), Some(time_limit), false) =
+            (self.start_time, self.time_limit, self.is_finished())
+        {
+            if now.saturating_duration_since(start_time) >= time_limit {
+                self.end_time = Some(start_time + time_limit);
+            }
+        }
+    }
+
+    pub fn remaining_time(&self) -> Option<std::time::Duration> {
+        self.time_limit
+            .map(|time_limit| time_limit.saturating_sub(self.duration()))
+    }
+
+    pub fn extend_target(&mut self, text: &str, separator: &str) {
+        if !self.target_text.is_empty() {
+            self.target_text.push_str(separator);
+        }
+        self.target_text.push_str(text);
+        self.target_text = self.target_text.nfc().to_string();
+        self.target_text_grapheme_count = self.target_text.graphemes(true).count();
+        self.revision += 1;
+    }
+
+    /// Types `c` at `time`, the moment the key press was read from the terminal.
+    pub fn input(&mut self, c: char, time: std::time::Instant) {
+        self.check_time_limit(time);
+
         if self.is_finished() {
             return;
         }
 
+        self.record_input(Input::Character(c), time);
+
+        if self.word_by_word && c == ' ' {
+            if let Some(word) = self.words().last() {
+                // Space only moves on from a word that has been started.
+                if word.typed.is_empty() {
+                    return;
+                }
+                // Space after the last word ends the test instead, unless mistakes have to be
+                // dealt with and the word is not typed in full or any word is wrong.
+                if word.start + word.target.len() >= self.target_text_grapheme_count {
+                    let is_word_correct = word.typed == word.target;
+                    if self.strictness == Strictness::Lenient
+                        || (is_word_correct && self.is_error_free())
+                    {
+                        self.finish(time);
+                    }
+                    return;
+                }
+            }
+        }
+
         if !self.is_started() {
-            self.start();
+            self.start(time);
         }
 
+        let next_position = self.target_position();
+        let previous_text = self.current_text.clone();
         self.current_text.push(c);
         self.normalize_current_text();
+        // Combining marks change the grapheme before them rather than typing one of their own.
+        let typed = self
+            .current_text
+            .graphemes(true)
+            .last()
+            .unwrap_or_default()
+            .to_string();
+
+        if self.strictness == Strictness::StopOnError && !self.is_error_free() {
+            // The mistake is logged, but the text stays as it was until the right grapheme is
+            // typed.
+            self.current_text = previous_text;
+            self.normalize_current_text();
+            self.record_keystroke(
+                KeystrokeKind::Input {
+                    position: next_position,
+                    typed,
+                    expected: self
+                        .target_text
+                        .graphemes(true)
+                        .nth(next_position)
+                        .map(str::to_string),
+                    is_correction: false,
+                },
+                time,
+            );
+            return;
+        }
+
+        let typed_position = self.current_text_grapheme_count.saturating_sub(1);
+        let is_correction = self.deleted_positions.contains(&typed_position);
+        let (position, expected) = self.expected_grapheme(typed_position);
+        let is_expected_newline = c == '\n' && expected.as_deref() == Some("\n");
+        self.record_keystroke(
+            KeystrokeKind::Input {
+                position,
+                typed,
+                expected,
+                is_correction,
+            },
+            time,
+        );
+
+        if is_expected_newline {
+            self.auto_indent();
+        }
+
+        if self.target_position() >= self.target_text_grapheme_count
+            && (self.strictness == Strictness::Lenient || self.is_error_free())
+        {
+            self.finish(time);
+        }
+    }
+
+    /// Types the indentation of the next target line after a newline, as code editors do.
+    fn auto_indent(&mut self) {
+        let indentation: String = self
+            .target_text
+            .graphemes(true)
+            .skip(self.target_position())
+            .take_while(|grapheme| *grapheme == " ")
+            .collect();
+        let start = self.current_text_grapheme_count;
+        self.current_text.push_str(&indentation);
+        self.normalize_current_text();
+        self.auto_indented
+            .extend(start..self.current_text_grapheme_count);
+    }
+
+    /// Whether code is being typed, where Enter types a newline instead of starting a new test.
+    pub fn accepts_newlines(&self) -> bool {
+        self.target_text.contains('\n')
+    }
 
-        if self.completion() >= 1.0 {
-            self.finish();
+    /// Index of the caret among the graphemes shown for the test: the typed graphemes, and the
+    /// graphemes of the target that were skipped.
+    pub fn caret_index(&self) -> usize {
+        self.alignment()
+            .steps
+            .iter()
+            .map(|step| match step.error {
+                Some(ErrorKind::Omission) => step.target.len(),
+                _ => step.typed.len(),
+            })
+            .sum()
+    }
+
+    /// Whether everything typed so far matches the target.
+    pub fn is_error_free(&self) -> bool {
+        self.alignment().error_counts().total() == 0
+    }
+
+    /// Position in the target text and expected grapheme of the grapheme at `typed_position` in
+    /// the current text, according to the alignment of both. Inserted graphemes are positioned
+    /// before the next target grapheme and expect nothing.
+    fn expected_grapheme(&self, typed_position: usize) -> (usize, Option<String>) {
+        let alignment = self.alignment();
+        let Some(step) = alignment.step_for_typed(typed_position) else {
+            return (typed_position, None);
+        };
+
+        let position = match step.error {
+            Some(ErrorKind::Insertion) => return (step.target.start, None),
+            // The two graphemes of a transposition are each paired with the other target one.
+            Some(ErrorKind::Transposition) => {
+                step.target.start + (step.typed.end - 1 - typed_position)
+            }
+            _ => step.target.start,
+        };
+
+        (
+            position,
+            self.target_text
+                .graphemes(true)
+                .nth(position)
+                .map(str::to_string),
+        )
+    }
+
+    /// Aligns the current text with the target, see [`alignment::align`]. In word-by-word mode,
+    /// every typed word is aligned with its own target word only. The alignment is only computed
+    /// again once the test has changed.
+    pub fn alignment(&self) -> std::cell::Ref<'_, Alignment> {
+        let key = (self.revision, self.is_finished(), self.word_by_word);
+        let is_stale = self
+            .alignment
+            .borrow()
+            .as_ref()
+            .map_or(true, |(cached_key, _)| *cached_key != key);
+        if is_stale {
+            *self.alignment.borrow_mut() = Some((key, self.compute_alignment()));
         }
+        std::cell::Ref::map(self.alignment.borrow(), |cache| match cache {
+            Some((_, alignment)) => alignment,
+            None => unreachable!("the alignment was just computed"),
+        })
     }
 
-    pub fn delete_character(&mut self) {
+    /// Classifies every grapheme of the target text as source code in `language`, see
+    /// [`highlight::highlight`]. The classes are only computed again once the target has grown.
+    pub fn token_kinds(&self, language: Option<&str>) -> std::cell::Ref<'_, [TokenKind]> {
+        let key = (self.target_text.len(), language.map(str::to_string));
+        let is_stale = self
+            .token_kinds
+            .borrow()
+            .as_ref()
+            .map_or(true, |(cached_key, _)| *cached_key != key);
+        if is_stale {
+            let token_kinds = highlight::highlight(&self.target_text, language);
+            *self.token_kinds.borrow_mut() = Some((key, token_kinds));
+        }
+        std::cell::Ref::map(self.token_kinds.borrow(), |cache| match cache {
+            Some((_, token_kinds)) => token_kinds.as_slice(),
+            None => unreachable!("the token kinds were just computed"),
+        })
+    }
+
+    fn compute_alignment(&self) -> Alignment {
+        if !self.word_by_word {
+            let target: Vec<&str> = self.target_text.graphemes(true).collect();
+            let typed: Vec<&str> = self.current_text.graphemes(true).collect();
+            let is_complete = self.is_finished() && self.time_limit.is_none();
+            return alignment::align(&target, &typed, is_complete);
+        }
+
+        let mut alignment = Alignment::default();
+        let mut typed_start = 0;
+
+        for word in self.words() {
+            alignment.extend_shifted(
+                alignment::align(&word.target, &word.typed, word.is_submitted),
+                word.start,
+                typed_start,
+            );
+            typed_start += word.typed.len();
+
+            if word.is_submitted {
+                let separator = word.start + word.target.len();
+                alignment.steps.push(Step {
+                    error: None,
+                    target: separator..separator + 1,
+                    typed: typed_start..typed_start + 1,
+                });
+                typed_start += 1;
+            }
+        }
+
+        alignment
+    }
+
+    /// Splits the target and the current text into words, pairing the n-th typed word with the
+    /// n-th target word, so that a skipped or extra letter only affects its own word. Target
+    /// words past the one being typed are left out.
+    pub fn words(&self) -> Vec<WordProgress<'_>> {
+        let typed_words: Vec<&str> = self.current_text.split(' ').collect();
+        let mut start = 0;
+
+        std::iter::zip(self.target_text.split(' '), typed_words.iter().enumerate())
+            .map(|(target, (index, typed))| {
+                let word = WordProgress {
+                    start,
+                    target: target.graphemes(true).collect(),
+                    typed: typed.graphemes(true).collect(),
+                    is_submitted: index + 1 < typed_words.len(),
+                };
+                start += word.target.len() + 1;
+                word
+            })
+            .collect()
+    }
+
+    /// Position in the target text of the next grapheme to type.
+    pub fn target_position(&self) -> usize {
+        self.alignment().target_end()
+    }
+
+    pub fn delete_character(&mut self, time: std::time::Instant) {
         if !self.is_finished() {
+            self.record_input(Input::DeleteCharacter, time);
             if let Some((byte_offset, _)) = self.current_text.grapheme_indices(true).last() {
+                let previous_grapheme_count = self.current_text_grapheme_count;
                 self.current_text.truncate(byte_offset);
                 self.normalize_current_text();
+                let span = self.current_text_grapheme_count..previous_grapheme_count;
+                self.deleted_positions.extend(span.clone());
+                self.record_keystroke(KeystrokeKind::DeleteCharacter { span }, time);
             }
         }
     }
 
-    pub fn delete_word(&mut self) {
+    pub fn delete_word(&mut self, time: std::time::Instant) {
         if !self.is_finished() {
+            self.record_input(Input::DeleteWord, time);
             if let Some((byte_offset, _)) = self.current_text.unicode_word_indices().last() {
+                let previous_grapheme_count = self.current_text_grapheme_count;
                 self.current_text.truncate(byte_offset);
                 self.normalize_current_text();
+                let span = self.current_text_grapheme_count..previous_grapheme_count;
+                self.deleted_positions.extend(span.clone());
+                self.record_keystroke(KeystrokeKind::DeleteWord { span }, time);
             }
         }
     }
 
+    /// Number of correct graphemes typed by the typist, leaving out auto-indentation.
     pub fn correct_graphemes(&self) -> usize {
-        std::iter::zip(
-            self.target_text.graphemes(true),
-            self.current_text.graphemes(true),
-        )
-        .filter(|(target, current)| target == current)
-        .count()
+        self.alignment()
+            .steps
+            .iter()
+            .filter(|step| step.error.is_none())
+            .flat_map(|step| step.typed.clone())
+            .filter(|position| !self.auto_indented.contains(position))
+            .count()
     }
 
+    /// Number of graphemes typed by the typist, leaving out auto-indentation.
+    pub fn typed_graphemes(&self) -> usize {
+        self.current_text_grapheme_count - self.auto_indented.len()
+    }
+
+    /// Share of correct graphemes among the typed ones, skipped graphemes of the target counting
+    /// as mistakes too.
     pub fn accuracy(&self) -> f64 {
-        if self.current_text_grapheme_count == 0 {
+        let attempted_graphemes =
+            self.typed_graphemes() + self.alignment().error_counts().omissions;
+        if attempted_graphemes == 0 {
             0.0
         } else {
-            self.correct_graphemes() as f64 / self.current_text_grapheme_count as f64
+            self.correct_graphemes() as f64 / attempted_graphemes as f64
         }
     }
 
     pub fn duration(&self) -> std::time::Duration {
+        self.duration_at(clock::now())
+    }
+
+    /// Time spent on the test as of `now`, which only matters while the test is running.
+    pub fn duration_at(&self, now: std::time::Instant) -> std::time::Duration {
         if let Some(start_time) = self.start_time {
-            self.end_time
-                .unwrap_or(std::time::Instant::now())
-                .duration_since(start_time)
+            let duration = self
+                .end_time
+                .unwrap_or(now)
+                .saturating_duration_since(start_time);
+            match self.time_limit {
+                Some(time_limit) => std::cmp::min(duration, time_limit),
+                None => duration,
+            }
         } else {
             std::time::Duration::default()
         }
@@ -261,16 +1595,37 @@ impl Test {
     }
 
     pub fn raw_wpm(&self) -> f64 {
-        self.calculate_wpm(self.current_text_grapheme_count)
+        self.calculate_wpm(self.typed_graphemes())
     }
 
     pub fn completion(&self) -> f64 {
-        self.current_text_grapheme_count as f64 / self.target_text_grapheme_count as f64
+        match self.time_limit {
+            Some(time_limit) => self.duration().as_secs_f64() / time_limit.as_secs_f64(),
+            None => self.target_position() as f64 / self.target_text_grapheme_count as f64,
+        }
+    }
+
+    fn record_keystroke(&mut self, kind: KeystrokeKind, time: std::time::Instant) {
+        self.keystrokes.push(Keystroke {
+            time: self.duration_at(time),
+            kind,
+        });
+    }
+
+    fn record_input(&mut self, input: Input, time: std::time::Instant) {
+        self.inputs.push(TimedInput {
+            time: self.duration_at(time),
+            input,
+        });
     }
 
     fn normalize_current_text(&mut self) {
         self.current_text = self.current_text.nfc().to_string();
         self.current_text_grapheme_count = self.current_text.graphemes(true).count();
+        // Deleted indentation is typed by the typist if typed again.
+        self.auto_indented
+            .split_off(&self.current_text_grapheme_count);
+        self.revision += 1;
     }
 
     fn calculate_wpm(&self, grapheme_count: usize) -> f64 {
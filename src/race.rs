@@ -0,0 +1,678 @@
+//! Races between typists on the same machine or network. The host runs a server, see [`serve`],
+//! that every player, the host included, connects to with a [`RaceClient`]. The host picks the
+//! text of every race, and the server relays the progress of every player to all of them.
+//!
+//! Players and server exchange [`Message`]s as JSON, one per line, over TCP or a Unix socket.
+
+use std::io::{BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::{Strictness, Test};
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+
+/// How long the server waits for a player to take a message before dropping them, so that a
+/// player who stopped reading cannot hold up the race for everyone else.
+const WRITE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// How long the server waits before accepting players again after failing to.
+const ACCEPT_BACKOFF: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Longest line read from the network, enough for the text of any race.
+const MAX_MESSAGE_LENGTH: u64 = 1 << 20;
+
+/// Where a race is hosted: `host:port` for TCP, or `unix:PATH` for a Unix socket.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Address {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+}
+
+impl std::str::FromStr for Address {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.strip_prefix("unix:") {
+            #[cfg(unix)]
+            Some(path) => Ok(Address::Unix(path.into())),
+            #[cfg(not(unix))]
+            Some(_) => Err("Unix sockets are not supported on this platform".to_string()),
+            None => Ok(Address::Tcp(text.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Address::Tcp(address) => f.write_str(address),
+            #[cfg(unix)]
+            Address::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+pub enum Listener {
+    Tcp(std::net::TcpListener),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener),
+}
+
+impl Listener {
+    pub fn bind(address: &Address) -> std::io::Result<Listener> {
+        match address {
+            Address::Tcp(address) => std::net::TcpListener::bind(address).map(Listener::Tcp),
+            #[cfg(unix)]
+            Address::Unix(path) => std::os::unix::net::UnixListener::bind(path).map(Listener::Unix),
+        }
+    }
+
+    /// Address that players on this machine can connect to.
+    pub fn local_address(&self) -> std::io::Result<Address> {
+        match self {
+            Listener::Tcp(listener) => {
+                let mut address = listener.local_addr()?;
+                if address.ip().is_unspecified() {
+                    address.set_ip(match address {
+                        std::net::SocketAddr::V4(_) => std::net::Ipv4Addr::LOCALHOST.into(),
+                        std::net::SocketAddr::V6(_) => std::net::Ipv6Addr::LOCALHOST.into(),
+                    });
+                }
+                Ok(Address::Tcp(address.to_string()))
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener) => listener
+                .local_addr()?
+                .as_pathname()
+                .map(|path| Address::Unix(path.to_path_buf()))
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "the Unix socket has no path",
+                    )
+                }),
+        }
+    }
+
+    fn accept(&self) -> std::io::Result<Stream> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nodelay(true)?;
+                Ok(Stream::Tcp(stream))
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener) => listener.accept().map(|(stream, _)| Stream::Unix(stream)),
+        }
+    }
+}
+
+enum Stream {
+    Tcp(std::net::TcpStream),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixStream),
+}
+
+impl Stream {
+    fn connect(address: &Address) -> std::io::Result<Stream> {
+        match address {
+            Address::Tcp(address) => {
+                let stream = std::net::TcpStream::connect(address)?;
+                stream.set_nodelay(true)?;
+                Ok(Stream::Tcp(stream))
+            }
+            #[cfg(unix)]
+            Address::Unix(path) => std::os::unix::net::UnixStream::connect(path).map(Stream::Unix),
+        }
+    }
+
+    fn try_clone(&self) -> std::io::Result<Stream> {
+        match self {
+            Stream::Tcp(stream) => stream.try_clone().map(Stream::Tcp),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.try_clone().map(Stream::Unix),
+        }
+    }
+
+    fn set_write_timeout(&self, timeout: Option<std::time::Duration>) -> std::io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.set_write_timeout(timeout),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_write_timeout(timeout),
+        }
+    }
+
+    /// Ends the connection, which also stops the thread reading from a clone of the stream.
+    fn shutdown(&self) -> std::io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.shutdown(std::net::Shutdown::Both),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.shutdown(std::net::Shutdown::Both),
+        }
+    }
+}
+
+impl std::io::Read for Stream {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buffer),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buffer),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buffer: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buffer),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buffer),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Everything every player needs to type the same test.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct RaceSettings {
+    pub corpus: String,
+    /// Number of words, or lines of code, the text was sampled with.
+    pub test_length: usize,
+    pub target_text: String,
+    pub time_limit: Option<std::time::Duration>,
+    pub word_by_word: bool,
+    pub strictness: Strictness,
+}
+
+/// How far a player is in the current race.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Progress {
+    /// See [`Test::completion`].
+    pub completion: f64,
+    pub wpm: f64,
+    pub accuracy: f64,
+    pub is_finished: bool,
+}
+
+impl Progress {
+    pub fn new(test: &Test) -> Self {
+        Progress {
+            completion: test.completion(),
+            wpm: test.wpm(),
+            accuracy: test.accuracy(),
+            is_finished: test.is_finished(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Racer {
+    pub id: u64,
+    pub name: String,
+    /// `None` for players who joined after the current race started.
+    pub progress: Option<Progress>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Message {
+    /// Sent by a player once connected.
+    Join { name: String },
+    /// Sent by the server in reply to [`Message::Join`].
+    Welcome { id: u64 },
+    /// Sent by the host to start a race, then by the server to every player.
+    Start(RaceSettings),
+    /// Sent by a player whenever their progress changes.
+    Progress(Progress),
+    /// Sent by the server whenever a player joins, leaves or makes progress. The host is the
+    /// player who joined first among the ones still there.
+    Standings {
+        host: Option<u64>,
+        racers: Vec<Racer>,
+    },
+}
+
+fn write_message(stream: &mut Stream, message: &Message) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    stream.flush()
+}
+
+/// Passes every message read from `stream` to `handle` until the stream ends, a line is not a
+/// message or is longer than [`MAX_MESSAGE_LENGTH`], or `handle` returns `false`.
+fn read_messages(stream: Stream, mut handle: impl FnMut(Message) -> bool) {
+    let mut reader = std::io::BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match std::io::Read::take(&mut reader, MAX_MESSAGE_LENGTH).read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            // Either too long, or cut short by the end of the stream.
+            Ok(_) if !line.ends_with('\n') => return,
+            Ok(_) => {}
+        }
+        let Ok(message) = serde_json::from_str(&line) else {
+            return;
+        };
+        if !handle(message) {
+            return;
+        }
+    }
+}
+
+enum ServerEvent {
+    Connected(u64, Stream),
+    Received(u64, Message),
+    Disconnected(u64),
+}
+
+struct Player {
+    id: u64,
+    /// `None` until the player has sent [`Message::Join`].
+    name: Option<String>,
+    stream: Stream,
+    progress: Option<Progress>,
+}
+
+/// Accepts players on `listener` and relays their messages, in background threads that run
+/// until the process exits.
+pub fn serve(listener: Listener) {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || accept_players(&listener, &sender));
+    std::thread::spawn(move || relay(&receiver));
+}
+
+fn accept_players(listener: &Listener, events: &std::sync::mpsc::Sender<ServerEvent>) {
+    for id in 0.. {
+        let stream = match listener.accept() {
+            Ok(stream) => stream,
+            Err(error) => {
+                // A player giving up while being accepted does not affect the next one, but
+                // errors such as running out of file descriptors last a while.
+                if !matches!(
+                    error.kind(),
+                    std::io::ErrorKind::ConnectionAborted | std::io::ErrorKind::Interrupted
+                ) {
+                    eprintln!("warning: cannot accept players: {}", error);
+                    std::thread::sleep(ACCEPT_BACKOFF);
+                }
+                continue;
+            }
+        };
+        if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_err() {
+            continue;
+        }
+        let Ok(reader) = stream.try_clone() else {
+            continue;
+        };
+        if events.send(ServerEvent::Connected(id, stream)).is_err() {
+            return;
+        }
+
+        let events = events.clone();
+        std::thread::spawn(move || {
+            read_messages(reader, |message| {
+                events.send(ServerEvent::Received(id, message)).is_ok()
+            });
+            let _ = events.send(ServerEvent::Disconnected(id));
+        });
+    }
+}
+
+fn relay(events: &std::sync::mpsc::Receiver<ServerEvent>) {
+    let mut players: Vec<Player> = Vec::new();
+
+    for event in events {
+        let host = players
+            .iter()
+            .find(|player| player.name.is_some())
+            .map(|player| player.id);
+
+        match event {
+            ServerEvent::Connected(id, stream) => players.push(Player {
+                id,
+                name: None,
+                stream,
+                progress: None,
+            }),
+            ServerEvent::Received(id, Message::Join { name }) => {
+                players.retain_mut(|player| {
+                    if player.id != id {
+                        return true;
+                    }
+                    player.name = Some(name.clone());
+                    send_or_drop(player, &Message::Welcome { id })
+                });
+            }
+            ServerEvent::Received(id, Message::Start(settings)) if host == Some(id) => {
+                for player in &mut players {
+                    player.progress = player.name.is_some().then(Progress::default);
+                }
+                broadcast(&mut players, &Message::Start(settings));
+            }
+            ServerEvent::Received(id, Message::Progress(progress)) => {
+                if let Some(player) = players
+                    .iter_mut()
+                    .find(|player| player.id == id && player.progress.is_some())
+                {
+                    player.progress = Some(progress);
+                }
+            }
+            ServerEvent::Received(..) => continue,
+            ServerEvent::Disconnected(id) => players.retain(|player| {
+                if player.id == id {
+                    let _ = player.stream.shutdown();
+                }
+                player.id != id
+            }),
+        }
+
+        let standings = Message::Standings {
+            host: players
+                .iter()
+                .find(|player| player.name.is_some())
+                .map(|player| player.id),
+            racers: players
+                .iter()
+                .filter_map(|player| {
+                    Some(Racer {
+                        id: player.id,
+                        name: player.name.clone()?,
+                        progress: player.progress,
+                    })
+                })
+                .collect(),
+        };
+        broadcast(&mut players, &standings);
+    }
+}
+
+/// Sends `message` to every player who joined, dropping the ones it cannot be sent to.
+fn broadcast(players: &mut Vec<Player>, message: &Message) {
+    players.retain_mut(|player| player.name.is_none() || send_or_drop(player, message));
+}
+
+/// Sends `message` to `player`, or ends their connection if it cannot be sent in time. Returns
+/// whether the player is still connected.
+fn send_or_drop(player: &mut Player, message: &Message) -> bool {
+    if write_message(&mut player.stream, message).is_ok() {
+        return true;
+    }
+    let _ = player.stream.shutdown();
+    false
+}
+
+pub enum RaceEvent {
+    /// The host started a race, which the player should type now.
+    Started(RaceSettings),
+    /// Players joined, left or made progress.
+    Updated,
+    Disconnected,
+}
+
+/// A player's connection to a race server.
+pub struct RaceClient {
+    stream: Stream,
+    /// Messages read from the server, `None` once the connection is lost.
+    messages: std::sync::mpsc::Receiver<Option<Message>>,
+    pub id: Option<u64>,
+    pub host: Option<u64>,
+    pub racers: Vec<Racer>,
+    pub is_connected: bool,
+    /// Whether a race started since the player joined.
+    pub is_racing: bool,
+    last_progress: Option<Progress>,
+}
+
+impl RaceClient {
+    /// Connects to the server at `address` and joins as `name`.
+    pub fn connect(address: &Address, name: &str) -> std::io::Result<RaceClient> {
+        let stream = Stream::connect(address).map_err(|error| {
+            std::io::Error::new(
+                error.kind(),
+                format!("cannot join the race at {}: {}", address, error),
+            )
+        })?;
+        let reader = stream.try_clone()?;
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            read_messages(reader, |message| sender.send(Some(message)).is_ok());
+            let _ = sender.send(None);
+        });
+
+        let mut client = RaceClient {
+            stream,
+            messages: receiver,
+            id: None,
+            host: None,
+            racers: Vec::new(),
+            is_connected: true,
+            is_racing: false,
+            last_progress: None,
+        };
+        client.send(&Message::Join {
+            name: name.to_string(),
+        })?;
+        Ok(client)
+    }
+
+    pub fn is_host(&self) -> bool {
+        self.id.is_some() && self.id == self.host
+    }
+
+    pub fn send(&mut self, message: &Message) -> std::io::Result<()> {
+        let result = write_message(&mut self.stream, message);
+        if result.is_err() {
+            self.is_connected = false;
+        }
+        result
+    }
+
+    /// Sends the progress of `test`, the test of the current race, if it changed since it was
+    /// last sent.
+    pub fn report(&mut self, test: &Test) -> std::io::Result<()> {
+        let progress = Progress::new(test);
+        if !self.is_connected || !self.is_racing || self.last_progress == Some(progress) {
+            return Ok(());
+        }
+        self.last_progress = Some(progress);
+        self.send(&Message::Progress(progress))
+    }
+
+    /// Handles the messages received since the last call, without waiting for more.
+    pub fn poll(&mut self) -> Vec<RaceEvent> {
+        let mut events = Vec::new();
+
+        while let Ok(message) = self.messages.try_recv() {
+            match message {
+                Some(Message::Welcome { id }) => {
+                    self.id = Some(id);
+                    events.push(RaceEvent::Updated);
+                }
+                Some(Message::Standings { host, racers }) => {
+                    self.host = host;
+                    self.racers = racers;
+                    events.push(RaceEvent::Updated);
+                }
+                Some(Message::Start(settings)) => {
+                    self.is_racing = true;
+                    self.last_progress = None;
+                    events.push(RaceEvent::Started(settings));
+                }
+                Some(Message::Join { .. } | Message::Progress(_)) => {}
+                None => {
+                    self.is_connected = false;
+                    events.push(RaceEvent::Disconnected);
+                }
+            }
+        }
+
+        events
+    }
+
+    /// Players of the current race, finished ones first, then by speed and progress. Players
+    /// waiting for the next race come last.
+    pub fn leaderboard(&self) -> Vec<&Racer> {
+        let mut racers: Vec<&Racer> = self.racers.iter().collect();
+        racers.sort_by(|a, b| match (a.progress, b.progress) {
+            (Some(a), Some(b)) => b.is_finished.cmp(&a.is_finished).then(if a.is_finished {
+                b.wpm.total_cmp(&a.wpm)
+            } else {
+                b.completion.total_cmp(&a.completion)
+            }),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        });
+        racers
+    }
+
+    /// Whether everyone in the current race has finished.
+    pub fn is_race_over(&self) -> bool {
+        let mut progress = self.racers.iter().filter_map(|racer| racer.progress);
+        self.is_racing && progress.all(|progress| progress.is_finished)
+    }
+}
+
+impl Drop for RaceClient {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown();
+    }
+}
+
+/// Name players join with when they do not pick one.
+pub fn default_name() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "player".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Polls `client` until `condition` holds, failing after a few seconds.
+    fn wait_for(
+        client: &mut RaceClient,
+        condition: impl Fn(&RaceClient) -> bool,
+    ) -> Vec<RaceEvent> {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        let mut events = Vec::new();
+        while !condition(client) {
+            assert!(std::time::Instant::now() < deadline, "timed out");
+            events.extend(client.poll());
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        events
+    }
+
+    fn settings() -> RaceSettings {
+        RaceSettings {
+            corpus: "Test".to_string(),
+            test_length: 2,
+            target_text: "ab cd".to_string(),
+            time_limit: None,
+            word_by_word: false,
+            strictness: Strictness::Lenient,
+        }
+    }
+
+    fn race(address: &Address) {
+        let listener = Listener::bind(address).unwrap();
+        let address = listener.local_address().unwrap();
+        serve(listener);
+
+        let mut host = RaceClient::connect(&address, "host").unwrap();
+        wait_for(&mut host, |client| client.is_host());
+        let mut guest = RaceClient::connect(&address, "guest").unwrap();
+        wait_for(&mut guest, |client| client.racers.len() == 2);
+        assert!(!guest.is_host());
+
+        // Only the host starts races.
+        guest.send(&Message::Start(settings())).unwrap();
+        host.send(&Message::Start(settings())).unwrap();
+        let events = wait_for(&mut guest, |client| client.is_racing);
+        let started: Vec<&RaceSettings> = events
+            .iter()
+            .filter_map(|event| match event {
+                RaceEvent::Started(settings) => Some(settings),
+                _ => None,
+            })
+            .collect();
+        assert!(started.len() == 1 && *started[0] == settings());
+
+        let start = std::time::Instant::now();
+        let mut test = Test::new(&settings().target_text);
+        for (index, c) in "ab cd".chars().enumerate() {
+            test.input(
+                c,
+                start + std::time::Duration::from_millis(100 * index as u64),
+            );
+        }
+        wait_for(&mut host, |client| client.is_racing);
+        host.report(&test).unwrap();
+
+        wait_for(&mut guest, |client| {
+            client.leaderboard()[0].name == "host"
+                && client.leaderboard()[0]
+                    .progress
+                    .is_some_and(|progress| progress.is_finished)
+        });
+        assert!(!guest.is_race_over());
+
+        guest.report(&test).unwrap();
+        wait_for(&mut host, |client| client.is_race_over());
+
+        // The guest becomes the host once the host leaves.
+        drop(host);
+        wait_for(&mut guest, |client| {
+            client.is_host() && client.racers.len() == 1
+        });
+    }
+
+    #[test]
+    fn race_over_tcp() {
+        race(&"127.0.0.1:0".parse().unwrap());
+    }
+
+    #[test]
+    fn overlong_messages_drop_the_player() {
+        let listener = Listener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let Address::Tcp(address) = listener.local_address().unwrap() else {
+            unreachable!();
+        };
+        serve(listener);
+
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+            .unwrap();
+        stream.write_all(b"{\"Join\":{\"name\":\"").unwrap();
+        // The server may hang up before taking all of it.
+        let _ = stream.write_all(&vec![b'a'; MAX_MESSAGE_LENGTH as usize + 1]);
+        let mut buffer = [0; 64];
+        match std::io::Read::read(&mut stream, &mut buffer) {
+            Ok(read) => assert_eq!(read, 0),
+            Err(error) => assert_ne!(error.kind(), std::io::ErrorKind::WouldBlock),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn race_over_unix_socket() {
+        let path = std::env::temp_dir().join(format!("ttl-race-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        race(&Address::Unix(path.clone()));
+        let _ = std::fs::remove_file(&path);
+    }
+}
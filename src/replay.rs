@@ -0,0 +1,219 @@
+//! Plays a recorded test back by feeding its inputs to a fresh [`Test`] at the pace they were
+//! made, so that the test screen can show where the typist hesitated or fumbled, or race the
+//! typist against a past run as a ghost.
+
+use std::time::{Duration, Instant};
+
+use crate::{
+    history::TestRecord,
+    model::{Input, Screen, Strictness, Test, TimedInput},
+};
+
+/// Playback speeds, in order, that the replay steps through.
+pub const SPEEDS: [f64; 4] = [0.5, 1.0, 2.0, 10.0];
+
+pub struct Replay {
+    /// The test as it was at [`Replay::position`].
+    pub test: Test,
+    pub corpus: String,
+    target_text: String,
+    time_limit: Option<Duration>,
+    word_by_word: bool,
+    strictness: Strictness,
+    inputs: Vec<TimedInput>,
+    /// Number of inputs fed to the test so far.
+    pub played: usize,
+    /// How long the recorded test took.
+    pub duration: Duration,
+    /// How far into the recorded test the replay is.
+    pub position: Duration,
+    /// Index into [`SPEEDS`].
+    speed: usize,
+    pub is_paused: bool,
+    /// When [`Replay::position`] was last brought up to date.
+    updated_at: Instant,
+    /// Where closing the replay goes back to.
+    pub return_screen: Screen,
+}
+
+impl Replay {
+    /// Starts playing `record` at `now`, or returns `None` if its inputs were not recorded.
+    pub fn new(record: &TestRecord, return_screen: Screen, now: Instant) -> Option<Self> {
+        if record.inputs.is_empty() {
+            return None;
+        }
+
+        let mut replay = Replay {
+            test: Test::new(""),
+            corpus: record.corpus.clone(),
+            target_text: record.target_text.clone(),
+            time_limit: record.time_limit,
+            word_by_word: record.word_by_word,
+            strictness: record.strictness,
+            inputs: record.inputs.clone(),
+            played: 0,
+            duration: record.duration,
+            position: Duration::ZERO,
+            speed: SPEEDS.iter().position(|&speed| speed == 1.0).unwrap_or(0),
+            is_paused: false,
+            updated_at: now,
+            return_screen,
+        };
+        replay.rewind();
+        Some(replay)
+    }
+
+    pub fn speed(&self) -> f64 {
+        SPEEDS[self.speed]
+    }
+
+    pub fn input_count(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.duration
+    }
+
+    /// Moves the replay forward to `now` and feeds the test every input made by then.
+    pub fn advance(&mut self, now: Instant) {
+        if !self.is_paused {
+            let elapsed = now.saturating_duration_since(self.updated_at);
+            self.position =
+                std::cmp::min(self.position + elapsed.mul_f64(self.speed()), self.duration);
+        }
+        self.updated_at = now;
+        self.play(now, self.inputs.len());
+    }
+
+    /// When the screen next changes: at the next input, when the duration shown next ticks over,
+    /// or at the end. `None` while paused.
+    pub fn next_timer(&self) -> Option<Instant> {
+        if self.is_paused || self.is_finished() {
+            return None;
+        }
+
+        let next_second = Duration::from_secs(self.position.as_secs() + 1);
+        let next_event = [
+            self.next_input_time(),
+            Some(next_second),
+            Some(self.duration),
+        ]
+        .into_iter()
+        .flatten()
+        .min()?;
+
+        Some(
+            self.updated_at
+                + next_event
+                    .saturating_sub(self.position)
+                    .div_f64(self.speed()),
+        )
+    }
+
+    /// Time of the next input to play, relative to the start of the recorded test.
+    pub fn next_input_time(&self) -> Option<Duration> {
+        self.inputs.get(self.played).map(|input| input.time)
+    }
+
+    /// Shows the recorded test as it was `position` into it, as a ghost following a test being
+    /// typed does.
+    pub fn seek(&mut self, position: Duration, now: Instant) {
+        if position < self.position {
+            self.rewind();
+        }
+        self.position = std::cmp::min(position, self.duration);
+        self.updated_at = now;
+        self.play(now, self.inputs.len());
+    }
+
+    pub fn toggle_pause(&mut self, now: Instant) {
+        if self.is_finished() {
+            self.restart(now);
+        } else {
+            self.is_paused = !self.is_paused;
+        }
+    }
+
+    /// Pauses and plays the next input.
+    pub fn step_forward(&mut self, now: Instant) {
+        self.is_paused = true;
+        self.position = match self.inputs.get(self.played) {
+            Some(input) => input.time,
+            None => self.duration,
+        };
+        self.play(now, self.played + 1);
+    }
+
+    /// Pauses and takes the last input back, or goes back to it if the replay has moved on since.
+    pub fn step_backward(&mut self, now: Instant) {
+        self.is_paused = true;
+        let last_played_time = self
+            .played
+            .checked_sub(1)
+            .map(|index| self.inputs[index].time);
+        let played = match last_played_time {
+            Some(time) if self.position > time => self.played,
+            _ => self.played.saturating_sub(1),
+        };
+
+        self.rewind();
+        if let Some(index) = played.checked_sub(1) {
+            self.position = self.inputs[index].time;
+        }
+        self.play(now, played);
+    }
+
+    pub fn increase_speed(&mut self) {
+        self.speed = std::cmp::min(self.speed + 1, SPEEDS.len() - 1);
+    }
+
+    pub fn decrease_speed(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+
+    pub fn restart(&mut self, now: Instant) {
+        self.rewind();
+        self.is_paused = false;
+        self.updated_at = now;
+        self.play(now, 0);
+    }
+
+    /// Goes back to the untouched test.
+    pub fn rewind(&mut self) {
+        self.test = match self.time_limit {
+            Some(time_limit) => Test::timed(&self.target_text, time_limit),
+            None => Test::new(&self.target_text),
+        };
+        self.test.word_by_word = self.word_by_word;
+        self.test.strictness = self.strictness;
+        self.played = 0;
+        self.position = Duration::ZERO;
+    }
+
+    /// Feeds the test the inputs made up to the current position, but no more than `limit` in
+    /// total. The test is moved in time so that, as seen from `now`, it started
+    /// [`Replay::position`] ago, which keeps its live statistics in step with the replay.
+    fn play(&mut self, now: Instant, limit: usize) {
+        let start_time = now.checked_sub(self.position).unwrap_or(now);
+        if self.test.is_started() && !self.test.is_finished() {
+            self.test.start_time = Some(start_time);
+        }
+
+        while let Some(input) = self.inputs[..limit.min(self.inputs.len())]
+            .get(self.played)
+            .copied()
+            .filter(|input| input.time <= self.position)
+        {
+            let time = start_time + input.time;
+            match input.input {
+                Input::Character(c) => self.test.input(c, time),
+                Input::DeleteCharacter => self.test.delete_character(time),
+                Input::DeleteWord => self.test.delete_word(time),
+            }
+            self.played += 1;
+        }
+
+        self.test.check_time_limit(start_time + self.position);
+    }
+}
@@ -0,0 +1,373 @@
+use crate::model::{KeystrokeKind, Test};
+
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Typing speed during one second of a test.
+pub struct SpeedSample {
+    pub second: usize,
+    pub wpm: f64,
+    pub raw_wpm: f64,
+}
+
+pub struct WordTiming {
+    pub word: String,
+    pub duration: std::time::Duration,
+    pub wpm: f64,
+}
+
+/// What the results screen shows of a finished test, computed once when it finishes rather than
+/// on every redraw.
+#[derive(Default)]
+pub struct TestResults {
+    pub speed_over_time: Vec<SpeedSample>,
+    pub consistency: Option<f64>,
+    pub errors_by_character: Vec<(String, usize)>,
+    pub slowest_words: Vec<WordTiming>,
+}
+
+impl TestResults {
+    pub fn new(test: &Test) -> Self {
+        TestResults {
+            speed_over_time: speed_over_time(test),
+            consistency: consistency(test),
+            errors_by_character: errors_by_character(test),
+            slowest_words: slowest_words(test, 10),
+        }
+    }
+}
+
+/// Splits the speed of `test` into one-second buckets, based on the keystroke log.
+pub fn speed_over_time(test: &Test) -> Vec<SpeedSample> {
+    let bucket_count = test.duration().as_secs_f64().ceil() as usize;
+    if bucket_count == 0 {
+        return Vec::new();
+    }
+
+    let mut correct = vec![0usize; bucket_count];
+    let mut typed = vec![0usize; bucket_count];
+
+    for keystroke in &test.keystrokes {
+        if let KeystrokeKind::Input {
+            typed: typed_grapheme,
+            expected,
+            ..
+        } = &keystroke.kind
+        {
+            let bucket = std::cmp::min(keystroke.time.as_secs() as usize, bucket_count - 1);
+            typed[bucket] += 1;
+            if expected.as_ref() == Some(typed_grapheme) {
+                correct[bucket] += 1;
+            }
+        }
+    }
+
+    (0..bucket_count)
+        .map(|second| SpeedSample {
+            second,
+            wpm: correct[second] as f64 / 5.0 * 60.0,
+            raw_wpm: typed[second] as f64 / 5.0 * 60.0,
+        })
+        .collect()
+}
+
+/// How steady the raw typing speed was over the test, from 0 (erratic) to 1 (perfectly even).
+/// Returns `None` for tests too short to tell.
+pub fn consistency(test: &Test) -> Option<f64> {
+    let samples = speed_over_time(test);
+    if samples.len() < 2 {
+        return None;
+    }
+
+    let mean = samples.iter().map(|sample| sample.raw_wpm).sum::<f64>() / samples.len() as f64;
+    if mean <= 0.0 {
+        return None;
+    }
+
+    let variance = samples
+        .iter()
+        .map(|sample| (sample.raw_wpm - mean).powi(2))
+        .sum::<f64>()
+        / samples.len() as f64;
+
+    Some((1.0 - variance.sqrt() / mean).max(0.0))
+}
+
+/// Counts the mistyped keystrokes per expected grapheme, most frequent first.
+pub fn errors_by_character(test: &Test) -> Vec<(String, usize)> {
+    let mut errors: Vec<(String, usize)> = Vec::new();
+
+    for keystroke in &test.keystrokes {
+        if let KeystrokeKind::Input {
+            typed,
+            expected: Some(expected),
+            ..
+        } = &keystroke.kind
+        {
+            if typed == expected {
+                continue;
+            }
+            match errors.iter_mut().find(|(grapheme, _)| grapheme == expected) {
+                Some((_, count)) => *count += 1,
+                None => errors.push((expected.clone(), 1)),
+            }
+        }
+    }
+
+    errors.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    errors
+}
+
+/// Times every fully typed word of the target, measured from the keystroke that ended the
+/// previous word, and returns the `count` slowest ones.
+pub fn slowest_words(test: &Test, count: usize) -> Vec<WordTiming> {
+    let last_input_time = |position: usize| {
+        test.keystrokes
+            .iter()
+            .rev()
+            .find_map(|keystroke| match &keystroke.kind {
+                KeystrokeKind::Input { position: p, .. } if *p == position => Some(keystroke.time),
+                _ => None,
+            })
+    };
+
+    let graphemes: Vec<&str> = test.target_text.graphemes(true).collect();
+    let is_space = |grapheme: &str| grapheme.trim().is_empty();
+
+    let mut timings = Vec::new();
+    let mut start = 0;
+
+    while start < graphemes.len() {
+        if is_space(graphemes[start]) {
+            start += 1;
+            continue;
+        }
+
+        let end = start
+            + graphemes[start..]
+                .iter()
+                .take_while(|grapheme| !is_space(grapheme))
+                .count();
+        let word = graphemes[start..end].concat();
+        let length = end - start;
+        let word_start = start;
+        start = end;
+
+        let begin = match word_start.checked_sub(1) {
+            Some(previous) => last_input_time(previous),
+            None => Some(std::time::Duration::default()),
+        };
+        let (Some(begin), Some(finish)) = (begin, last_input_time(end - 1)) else {
+            continue;
+        };
+        let Some(duration) = finish.checked_sub(begin) else {
+            continue;
+        };
+        if duration.is_zero() {
+            continue;
+        }
+
+        timings.push(WordTiming {
+            word,
+            duration,
+            wpm: length as f64 / 5.0 * 60.0 / duration.as_secs_f64(),
+        });
+    }
+
+    timings.sort_by(|a, b| a.wpm.total_cmp(&b.wpm));
+    timings.truncate(count);
+    timings
+}
+
+/// Accuracy and speed of typing one grapheme, summed over any number of tests.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct KeyStatistics {
+    /// Keystrokes made when this grapheme was expected.
+    pub presses: usize,
+    pub errors: usize,
+    /// Sum of the times elapsed since the previous keystroke, over the correct presses.
+    pub total_latency: std::time::Duration,
+    pub latency_samples: usize,
+}
+
+impl KeyStatistics {
+    pub fn error_rate(&self) -> Option<f64> {
+        (self.presses > 0).then(|| self.errors as f64 / self.presses as f64)
+    }
+
+    pub fn mean_latency(&self) -> Option<std::time::Duration> {
+        (self.latency_samples > 0).then(|| self.total_latency / self.latency_samples as u32)
+    }
+
+    pub fn add(&mut self, other: &KeyStatistics) {
+        self.presses += other.presses;
+        self.errors += other.errors;
+        self.total_latency += other.total_latency;
+        self.latency_samples += other.latency_samples;
+    }
+}
+
+/// Aggregates the keystroke log of `test` per expected grapheme.
+pub fn key_statistics(test: &Test) -> std::collections::BTreeMap<String, KeyStatistics> {
+    let mut statistics = std::collections::BTreeMap::<String, KeyStatistics>::new();
+    let mut previous_time = None;
+
+    for keystroke in &test.keystrokes {
+        if let KeystrokeKind::Input {
+            typed,
+            expected: Some(expected),
+            ..
+        } = &keystroke.kind
+        {
+            let key = statistics.entry(expected.clone()).or_default();
+            key.presses += 1;
+            if typed == expected {
+                if let Some(previous_time) = previous_time {
+                    key.total_latency += keystroke.time.saturating_sub(previous_time);
+                    key.latency_samples += 1;
+                }
+            } else {
+                key.errors += 1;
+            }
+        }
+        previous_time = Some(keystroke.time);
+    }
+
+    statistics
+}
+
+/// Aggregates the keystroke log of `test` per pair of consecutive expected graphemes, so that
+/// awkward transitions show up even when both graphemes are fine on their own.
+pub fn transition_statistics(test: &Test) -> std::collections::BTreeMap<String, KeyStatistics> {
+    let target_graphemes: Vec<&str> = test.target_text.graphemes(true).collect();
+    let mut statistics = std::collections::BTreeMap::<String, KeyStatistics>::new();
+    let mut previous: Option<(usize, std::time::Duration)> = None;
+
+    for keystroke in &test.keystrokes {
+        if let KeystrokeKind::Input {
+            position,
+            typed,
+            expected: Some(expected),
+            ..
+        } = &keystroke.kind
+        {
+            if let Some(previous_grapheme) = position
+                .checked_sub(1)
+                .and_then(|previous_position| target_graphemes.get(previous_position))
+            {
+                let transition = statistics
+                    .entry(format!("{}{}", previous_grapheme, expected))
+                    .or_default();
+                transition.presses += 1;
+                if typed != expected {
+                    transition.errors += 1;
+                } else if let Some((previous_position, previous_time)) = previous {
+                    if previous_position + 1 == *position {
+                        transition.total_latency += keystroke.time.saturating_sub(previous_time);
+                        transition.latency_samples += 1;
+                    }
+                }
+            }
+            previous = Some((*position, keystroke.time));
+        } else {
+            previous = None;
+        }
+    }
+
+    statistics
+}
+
+pub fn merge_statistics(
+    total: &mut std::collections::BTreeMap<String, KeyStatistics>,
+    statistics: &std::collections::BTreeMap<String, KeyStatistics>,
+) {
+    for (key, key_statistics) in statistics {
+        total.entry(key.clone()).or_default().add(key_statistics);
+    }
+}
+
+/// How much practice every grapheme and grapheme pair needs, derived from how often it was
+/// mistyped and how slowly it was typed compared to the typist's average.
+#[derive(Default)]
+pub struct PracticeWeights {
+    graphemes: std::collections::HashMap<String, f64>,
+    transitions: std::collections::HashMap<String, f64>,
+}
+
+impl PracticeWeights {
+    /// Weakness given to graphemes and transitions that were never typed, so that they get
+    /// sampled now and then.
+    const UNKNOWN_WEAKNESS: f64 = 0.5;
+    /// Number of presses needed before the statistics of an item are fully trusted.
+    const CONFIDENT_PRESSES: f64 = 10.0;
+
+    pub fn new(
+        key_statistics: &std::collections::BTreeMap<String, KeyStatistics>,
+        transition_statistics: &std::collections::BTreeMap<String, KeyStatistics>,
+    ) -> Self {
+        PracticeWeights {
+            graphemes: Self::weaknesses(key_statistics),
+            transitions: Self::weaknesses(transition_statistics),
+        }
+    }
+
+    /// Relative likelihood of picking `word` for practice, always at least 1.
+    pub fn word_weight(&self, word: &str) -> f64 {
+        let graphemes: Vec<&str> = word.graphemes(true).collect();
+        if graphemes.is_empty() {
+            return 1.0;
+        }
+
+        let weakness_of = |weaknesses: &std::collections::HashMap<String, f64>, key: String| {
+            weaknesses
+                .get(&key)
+                .copied()
+                .unwrap_or(Self::UNKNOWN_WEAKNESS)
+        };
+
+        let grapheme_weakness: f64 = graphemes
+            .iter()
+            .map(|grapheme| weakness_of(&self.graphemes, grapheme.to_string()))
+            .sum();
+        let transition_weakness: f64 = graphemes
+            .windows(2)
+            .map(|pair| weakness_of(&self.transitions, pair.concat()))
+            .sum();
+
+        let mean_weakness =
+            (grapheme_weakness + transition_weakness) / (2 * graphemes.len() - 1) as f64;
+        1.0 + 4.0 * mean_weakness
+    }
+
+    fn weaknesses(
+        statistics: &std::collections::BTreeMap<String, KeyStatistics>,
+    ) -> std::collections::HashMap<String, f64> {
+        let latencies: Vec<f64> = statistics
+            .values()
+            .filter_map(KeyStatistics::mean_latency)
+            .map(|latency| latency.as_secs_f64())
+            .collect();
+        let average_latency = if latencies.is_empty() {
+            0.0
+        } else {
+            latencies.iter().sum::<f64>() / latencies.len() as f64
+        };
+
+        statistics
+            .iter()
+            .map(|(key, key_statistics)| {
+                let error_weakness = key_statistics.error_rate().unwrap_or(0.0) * 10.0;
+                let latency_weakness = match key_statistics.mean_latency() {
+                    Some(latency) if average_latency > 0.0 => {
+                        (latency.as_secs_f64() / average_latency - 1.0).max(0.0) * 2.0
+                    }
+                    _ => 0.0,
+                };
+                let confidence = (key_statistics.presses as f64 / Self::CONFIDENT_PRESSES).min(1.0);
+                let weakness = confidence * (error_weakness + latency_weakness)
+                    + (1.0 - confidence) * Self::UNKNOWN_WEAKNESS;
+                (key.clone(), weakness)
+            })
+            .collect()
+    }
+}
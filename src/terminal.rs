@@ -1,9 +1,13 @@
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    event::{self, Event, KeyEvent, KeyEventKind},
     ExecutableCommand,
 };
 
-use crate::action::Action;
+use crate::{
+    action::{Action, TimedAction},
+    keymap::{KeyBinding, Keymap},
+    model::{CaretStyle, Screen},
+};
 
 pub fn create_terminal(
 ) -> std::io::Result<ratatui::prelude::Terminal<ratatui::prelude::CrosstermBackend<std::io::Stdout>>>
@@ -20,46 +24,59 @@ pub fn create_terminal(
     Ok(terminal)
 }
 
-pub fn poll_terminal() -> Option<Action> {
-    let Ok(true) = event::poll(std::time::Duration::from_millis(1)) else {
-        return None;
+/// Waits for the next terminal event, for at most `timeout` if one is given, and maps it to an
+/// action through `keymap`, stamped with the time the event was read. `accepts_newlines` makes
+/// Enter type a newline on the test screen, for code tests.
+pub fn poll_terminal(
+    keymap: &Keymap,
+    screen: Screen,
+    accepts_newlines: bool,
+    timeout: Option<std::time::Duration>,
+) -> Option<TimedAction> {
+    if let Some(timeout) = timeout {
+        let Ok(true) = event::poll(timeout) else {
+            return None;
+        };
+    }
+
+    let event = event::read();
+    let time = crate::clock::now();
+
+    let key = match event {
+        Ok(Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            state: _,
+        })) => KeyBinding::from_event(code, modifiers),
+        Ok(Event::Resize(_, _)) => return Some(TimedAction::now(Action::Resize)),
+        _ => return None,
     };
 
-    let Ok(Event::Key(KeyEvent {
-        code,
-        modifiers,
-        kind: KeyEventKind::Press,
-        state: _,
-    })) = event::read()
-    else {
-        return None;
+    let action = keymap.action(screen, key, accepts_newlines)?;
+
+    Some(TimedAction { action, time })
+}
+
+/// Shapes the terminal cursor, which ratatui places on the caret.
+pub fn set_caret_style(caret_style: CaretStyle, blink: bool) -> std::io::Result<()> {
+    use crossterm::cursor::SetCursorStyle;
+
+    let cursor_style = match (caret_style, blink) {
+        (CaretStyle::Block, false) => SetCursorStyle::SteadyBlock,
+        (CaretStyle::Block, true) => SetCursorStyle::BlinkingBlock,
+        (CaretStyle::Underline, false) => SetCursorStyle::SteadyUnderScore,
+        (CaretStyle::Underline, true) => SetCursorStyle::BlinkingUnderScore,
+        (CaretStyle::Bar, false) => SetCursorStyle::SteadyBar,
+        (CaretStyle::Bar, true) => SetCursorStyle::BlinkingBar,
     };
+    std::io::stdout().execute(cursor_style)?;
 
-    match (modifiers, code) {
-        (KeyModifiers::NONE, KeyCode::Esc)
-        | (KeyModifiers::CONTROL, KeyCode::Char('c'))
-        | (KeyModifiers::CONTROL, KeyCode::Char('q')) => Some(Action::Quit),
-        (KeyModifiers::CONTROL, KeyCode::Char('s')) => Some(Action::ToggleFrameStatistics),
-        (KeyModifiers::CONTROL, KeyCode::Char('l')) => Some(Action::ToggleLiveTypingStatistics),
-        (KeyModifiers::NONE, KeyCode::Tab) => Some(Action::Restart),
-        (KeyModifiers::NONE, KeyCode::Enter) => Some(Action::NextTest),
-        (KeyModifiers::NONE, KeyCode::Left) => Some(Action::PreviousCorpus),
-        (KeyModifiers::NONE, KeyCode::Right) => Some(Action::NextCorpus),
-        (KeyModifiers::NONE, KeyCode::Up) => Some(Action::IncreaseTestLength),
-        (KeyModifiers::NONE, KeyCode::Down) => Some(Action::DecreaseTestLength),
-        (KeyModifiers::NONE, KeyCode::Backspace) | (KeyModifiers::CONTROL, KeyCode::Char('h')) => {
-            Some(Action::DeleteCharacter)
-        }
-        (KeyModifiers::CONTROL, KeyCode::Backspace)
-        | (KeyModifiers::CONTROL, KeyCode::Char('w')) => Some(Action::DeleteWord),
-        (KeyModifiers::NONE, KeyCode::Char(c)) | (KeyModifiers::SHIFT, KeyCode::Char(c)) => {
-            Some(Action::CharacterInput(c))
-        }
-        _ => None,
-    }
+    Ok(())
 }
 
 pub fn destroy_terminal() -> std::io::Result<()> {
+    std::io::stdout().execute(crossterm::cursor::SetCursorStyle::DefaultUserShape)?;
     std::io::stdout().execute(crossterm::terminal::LeaveAlternateScreen)?;
     crossterm::terminal::disable_raw_mode()?;
 
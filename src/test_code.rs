@@ -0,0 +1,191 @@
+use crate::{corpus::SamplingMethod, model::TestMode};
+
+/// Everything needed to sample the exact same target text again, in a form short enough to be
+/// pasted into a chat: `<corpus>.<length>.<sampling>.<seed>`, e.g. `words.50.r.2hx9c3k1`.
+///
+/// The length is a word count, or a number of seconds followed by `s` for timed tests. Adaptive
+/// tests depend on the typist's own history, so they have no code.
+#[derive(Clone, PartialEq, Eq)]
+pub struct TestCode {
+    /// See [`Corpus::code_name`](crate::corpus::Corpus::code_name).
+    pub corpus: String,
+    pub test_mode: TestMode,
+    pub test_length: usize,
+    pub time_limit: std::time::Duration,
+    pub sampling_method: SamplingMethod,
+    pub seed: u64,
+}
+
+impl std::fmt::Display for TestCode {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let length = match self.test_mode {
+            TestMode::Words => self.test_length.to_string(),
+            TestMode::Time => format!("{}s", self.time_limit.as_secs()),
+        };
+        let sampling = match self.sampling_method {
+            SamplingMethod::Top => "t",
+            SamplingMethod::Random | SamplingMethod::Adaptive => "r",
+        };
+
+        write!(
+            formatter,
+            "{}.{}.{}.{}",
+            self.corpus,
+            length,
+            sampling,
+            to_base36(self.seed)
+        )
+    }
+}
+
+impl std::str::FromStr for TestCode {
+    type Err = String;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid test code '{}'", code);
+
+        // The corpus name may itself contain dots, so the code is split from the right.
+        let mut parts = code.trim().rsplitn(4, '.');
+        let seed = parts.next().and_then(from_base36).ok_or_else(invalid)?;
+        let sampling_method = match parts.next() {
+            Some("t") => SamplingMethod::Top,
+            Some("r") => SamplingMethod::Random,
+            _ => return Err(invalid()),
+        };
+        let length = parts.next().ok_or_else(invalid)?;
+        let corpus = parts
+            .next()
+            .filter(|corpus| !corpus.is_empty())
+            .ok_or_else(invalid)?
+            .to_string();
+
+        let (test_mode, test_length, time_limit) = match length.strip_suffix('s') {
+            Some(seconds) => (
+                TestMode::Time,
+                0,
+                std::time::Duration::from_secs(
+                    seconds
+                        .parse()
+                        .ok()
+                        .filter(|&seconds| seconds > 0)
+                        .ok_or_else(invalid)?,
+                ),
+            ),
+            None => (
+                TestMode::Words,
+                length
+                    .parse()
+                    .ok()
+                    .filter(|&length| length > 0)
+                    .ok_or_else(invalid)?,
+                std::time::Duration::default(),
+            ),
+        };
+
+        Ok(TestCode {
+            corpus,
+            test_mode,
+            test_length,
+            time_limit,
+            sampling_method,
+            seed,
+        })
+    }
+}
+
+fn to_base36(mut value: u64) -> String {
+    const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    let mut digits = Vec::new();
+    loop {
+        digits.push(DIGITS[(value % 36) as usize]);
+        value /= 36;
+        if value == 0 {
+            break;
+        }
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap_or_default()
+}
+
+fn from_base36(text: &str) -> Option<u64> {
+    u64::from_str_radix(text, 36).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        corpus::Corpus,
+        history::History,
+        model::{Config, Model},
+        theme::Theme,
+    };
+
+    #[test]
+    fn codes_round_trip() {
+        for code in [
+            "words.50.r.2hx9c3k1",
+            "english.top.200.bigrams.30s.t.0",
+            "rust.1.r.zz",
+        ] {
+            let parsed: TestCode = code.parse().unwrap();
+            assert_eq!(parsed.to_string(), code);
+        }
+
+        let code: TestCode = "words.15s.t.a".parse().unwrap();
+        assert!(code.test_mode == TestMode::Time);
+        assert_eq!(code.time_limit, std::time::Duration::from_secs(15));
+        assert!(code.sampling_method == SamplingMethod::Top);
+        assert_eq!(code.seed, 10);
+    }
+
+    #[test]
+    fn invalid_codes_are_rejected() {
+        for code in [
+            "",
+            "words",
+            "words.50.r",
+            ".50.r.1",
+            "words.0.r.1",
+            "words.0s.r.1",
+            "words.fifty.r.1",
+            "words.50.a.1",
+            "words.50.r.1!",
+        ] {
+            assert_eq!(
+                code.parse::<TestCode>().err(),
+                Some(format!("invalid test code '{}'", code))
+            );
+        }
+    }
+
+    #[test]
+    fn codes_reproduce_the_text() {
+        let model = |seed| {
+            let config = Config {
+                seed: Some(seed),
+                ..Config::default()
+            };
+            Model::new(
+                config,
+                Corpus::all_embedded(),
+                Theme::all_builtin(),
+                History::in_memory(),
+            )
+        };
+        let shared = model(1);
+        let code = shared.test_code().unwrap();
+
+        let mut other = model(2);
+        assert_ne!(
+            other.current_test.target_text,
+            shared.current_test.target_text
+        );
+        other.start_test_from_code(&code).unwrap();
+        assert_eq!(
+            other.current_test.target_text,
+            shared.current_test.target_text
+        );
+    }
+}
@@ -0,0 +1,559 @@
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use serde::Deserialize;
+
+use crate::highlight::TokenKind;
+
+/// Colours and text attributes of every styled element of the interface.
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    /// File the theme was loaded from, `None` for built-in themes.
+    pub path: Option<std::path::PathBuf>,
+
+    /// Target text that was not typed yet.
+    pub target: Style,
+    pub correct: Style,
+    pub incorrect: Style,
+    pub missing: Style,
+    pub extra: Style,
+    pub transposed: Style,
+    /// Where the ghost of a past run is in the target text.
+    pub ghost: Style,
+
+    /// Untyped code, by token. Plain tokens use [`Theme::target`].
+    pub keyword: Style,
+    pub type_name: Style,
+    pub string: Style,
+    pub comment: Style,
+    pub number: Style,
+    pub punctuation: Style,
+
+    pub label: Style,
+    pub value: Style,
+    /// Keys in the help text.
+    pub shortcut: Style,
+    /// What the keys do in the help text.
+    pub action: Style,
+    pub header: Style,
+    pub tab: Style,
+    pub selected_tab: Style,
+    pub selected_row: Style,
+    pub success: Style,
+    /// Status messages, usually errors.
+    pub status: Style,
+    /// Placeholders such as an empty history.
+    pub muted: Style,
+    pub wpm_line: Style,
+    pub raw_wpm_line: Style,
+    /// Background of the progress bar, whose foreground follows the accuracy.
+    pub gauge: Style,
+    /// Keys of the heatmap that have statistics, drawn over the heatmap colour.
+    pub heatmap_key: Style,
+    /// Keys of the heatmap that were never typed.
+    pub heatmap_unused: Style,
+
+    /// Styles of the live statistics, from the highest minimum accuracy to the lowest.
+    pub accuracy: Vec<AccuracyLevel>,
+    /// Heatmap gradient, from good to bad.
+    pub heatmap: [Color; 3],
+}
+
+#[derive(Clone, Copy)]
+pub struct AccuracyLevel {
+    /// Lowest accuracy, between 0 and 1, shown in this style.
+    pub minimum: f64,
+    pub style: Style,
+}
+
+/// On-disk representation of a [`Theme`]. Elements missing from `styles` keep the style of the
+/// `base` theme, the default one if none is given.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    name: Option<String>,
+    base: Option<String>,
+    #[serde(default)]
+    styles: std::collections::BTreeMap<String, StyleFile>,
+    accuracy: Option<Vec<AccuracyLevelFile>>,
+    heatmap: Option<[String; 3]>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StyleFile {
+    fg: Option<String>,
+    bg: Option<String>,
+    #[serde(default)]
+    modifiers: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccuracyLevelFile {
+    minimum: f64,
+    style: StyleFile,
+}
+
+fn parse_color(color: &str) -> Result<Color, String> {
+    color
+        .parse()
+        .map_err(|_| format!("unknown colour '{}'", color))
+}
+
+impl StyleFile {
+    fn to_style(&self) -> Result<Style, String> {
+        let mut style = Style::default();
+        if let Some(fg) = &self.fg {
+            style = style.fg(parse_color(fg)?);
+        }
+        if let Some(bg) = &self.bg {
+            style = style.bg(parse_color(bg)?);
+        }
+        for modifier in &self.modifiers {
+            let modifier = Modifier::from_name(&modifier.to_uppercase())
+                .ok_or_else(|| format!("unknown modifier '{}'", modifier))?;
+            style = style.add_modifier(modifier);
+        }
+        Ok(style)
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            name: "Default".to_string(),
+            path: None,
+            target: Style::default().fg(Color::DarkGray),
+            correct: Style::default().fg(Color::LightGreen),
+            incorrect: Style::default().fg(Color::LightRed).underlined(),
+            missing: Style::default().fg(Color::Red),
+            extra: Style::default().fg(Color::LightRed).crossed_out(),
+            transposed: Style::default().fg(Color::Yellow).underlined(),
+            ghost: Style::default().fg(Color::Black).bg(Color::Magenta),
+            keyword: Style::default().fg(Color::Blue),
+            type_name: Style::default().fg(Color::Cyan),
+            string: Style::default().fg(Color::Yellow),
+            comment: Style::default().fg(Color::DarkGray).italic(),
+            number: Style::default().fg(Color::Magenta),
+            punctuation: Style::default().fg(Color::Gray),
+            label: Style::default().fg(Color::White),
+            value: Style::default().fg(Color::Cyan),
+            shortcut: Style::default().fg(Color::Yellow),
+            action: Style::default().fg(Color::Blue),
+            header: Style::default().fg(Color::Yellow),
+            tab: Style::default().fg(Color::White),
+            selected_tab: Style::default().fg(Color::Cyan).underlined(),
+            selected_row: Style::default().fg(Color::Black).bg(Color::Cyan),
+            success: Style::default().fg(Color::LightGreen),
+            status: Style::default().fg(Color::LightRed),
+            muted: Style::default().fg(Color::DarkGray),
+            wpm_line: Style::default().fg(Color::Cyan),
+            raw_wpm_line: Style::default().fg(Color::DarkGray),
+            gauge: Style::default().bg(Color::Black),
+            heatmap_key: Style::default().fg(Color::Black),
+            heatmap_unused: Style::default().fg(Color::White).bg(Color::DarkGray),
+            accuracy: vec![
+                AccuracyLevel {
+                    minimum: 1.0,
+                    style: Style::default().fg(Color::LightGreen),
+                },
+                AccuracyLevel {
+                    minimum: 0.95,
+                    style: Style::default().fg(Color::Green),
+                },
+                AccuracyLevel {
+                    minimum: 0.75,
+                    style: Style::default().fg(Color::Yellow),
+                },
+                AccuracyLevel {
+                    minimum: 0.0,
+                    style: Style::default().fg(Color::Red),
+                },
+            ],
+            heatmap: [
+                Color::Rgb(0, 200, 60),
+                Color::Rgb(230, 200, 60),
+                Color::Rgb(230, 0, 60),
+            ],
+        }
+    }
+}
+
+impl Theme {
+    /// Bright colours and bold text, with errors on a coloured background.
+    pub fn high_contrast() -> Self {
+        Theme {
+            name: "High Contrast".to_string(),
+            target: Style::default().fg(Color::Gray),
+            correct: Style::default().fg(Color::White).bold(),
+            incorrect: Style::default().fg(Color::Black).bg(Color::LightRed),
+            missing: Style::default().fg(Color::LightRed).bold().underlined(),
+            extra: Style::default()
+                .fg(Color::Black)
+                .bg(Color::LightRed)
+                .crossed_out(),
+            transposed: Style::default().fg(Color::Black).bg(Color::LightYellow),
+            ghost: Style::default().fg(Color::Black).bg(Color::LightMagenta),
+            keyword: Style::default().fg(Color::LightBlue).bold(),
+            type_name: Style::default().fg(Color::LightCyan),
+            string: Style::default().fg(Color::LightYellow),
+            comment: Style::default().fg(Color::Gray).italic(),
+            number: Style::default().fg(Color::LightMagenta),
+            punctuation: Style::default().fg(Color::White),
+            value: Style::default().fg(Color::LightCyan).bold(),
+            shortcut: Style::default().fg(Color::LightYellow).bold(),
+            action: Style::default().fg(Color::White),
+            header: Style::default().fg(Color::LightYellow).bold(),
+            selected_tab: Style::default().fg(Color::LightCyan).bold().underlined(),
+            selected_row: Style::default().fg(Color::Black).bg(Color::White),
+            success: Style::default().fg(Color::LightGreen).bold(),
+            status: Style::default().fg(Color::LightRed).bold(),
+            muted: Style::default().fg(Color::Gray),
+            wpm_line: Style::default().fg(Color::LightCyan),
+            raw_wpm_line: Style::default().fg(Color::Gray),
+            accuracy: vec![
+                AccuracyLevel {
+                    minimum: 1.0,
+                    style: Style::default().fg(Color::LightGreen).bold(),
+                },
+                AccuracyLevel {
+                    minimum: 0.95,
+                    style: Style::default().fg(Color::LightGreen),
+                },
+                AccuracyLevel {
+                    minimum: 0.75,
+                    style: Style::default().fg(Color::LightYellow),
+                },
+                AccuracyLevel {
+                    minimum: 0.0,
+                    style: Style::default().fg(Color::LightRed).bold(),
+                },
+            ],
+            heatmap: [
+                Color::Rgb(0, 255, 0),
+                Color::Rgb(255, 255, 0),
+                Color::Rgb(255, 0, 0),
+            ],
+            ..Theme::default()
+        }
+    }
+
+    /// Blues against oranges from the Okabe-Ito palette, which stay apart with red-green colour
+    /// blindness. Errors are also told apart by their attributes.
+    pub fn colour_blind_safe() -> Self {
+        let sky_blue = Color::Rgb(86, 180, 233);
+        let blue = Color::Rgb(0, 114, 178);
+        let orange = Color::Rgb(230, 159, 0);
+        let vermillion = Color::Rgb(213, 94, 0);
+        let yellow = Color::Rgb(240, 228, 66);
+
+        Theme {
+            name: "Colour-blind Safe".to_string(),
+            correct: Style::default().fg(sky_blue),
+            incorrect: Style::default().fg(orange).bold().underlined(),
+            missing: Style::default().fg(vermillion).reversed(),
+            extra: Style::default().fg(orange).crossed_out(),
+            transposed: Style::default().fg(yellow).underlined(),
+            ghost: Style::default()
+                .fg(Color::Black)
+                .bg(Color::Rgb(204, 121, 167)),
+            keyword: Style::default().fg(blue),
+            type_name: Style::default().fg(sky_blue),
+            string: Style::default().fg(Color::Rgb(0, 158, 115)),
+            number: Style::default().fg(Color::Rgb(204, 121, 167)),
+            value: Style::default().fg(sky_blue),
+            shortcut: Style::default().fg(orange),
+            action: Style::default().fg(sky_blue),
+            header: Style::default().fg(orange),
+            selected_tab: Style::default().fg(sky_blue).underlined(),
+            selected_row: Style::default().fg(Color::Black).bg(sky_blue),
+            success: Style::default().fg(sky_blue),
+            status: Style::default().fg(vermillion),
+            wpm_line: Style::default().fg(sky_blue),
+            accuracy: vec![
+                AccuracyLevel {
+                    minimum: 1.0,
+                    style: Style::default().fg(sky_blue),
+                },
+                AccuracyLevel {
+                    minimum: 0.95,
+                    style: Style::default().fg(blue),
+                },
+                AccuracyLevel {
+                    minimum: 0.75,
+                    style: Style::default().fg(orange),
+                },
+                AccuracyLevel {
+                    minimum: 0.0,
+                    style: Style::default().fg(vermillion),
+                },
+            ],
+            heatmap: [blue, yellow, vermillion],
+            ..Theme::default()
+        }
+    }
+
+    /// Text attributes and shades of grey only, for terminals with few or unreliable colours.
+    pub fn monochrome() -> Self {
+        Theme {
+            name: "Monochrome".to_string(),
+            target: Style::default().dim(),
+            correct: Style::default().bold(),
+            incorrect: Style::default().reversed(),
+            missing: Style::default().underlined(),
+            extra: Style::default().crossed_out(),
+            transposed: Style::default().italic().underlined(),
+            ghost: Style::default().italic().reversed(),
+            keyword: Style::default().dim().bold(),
+            type_name: Style::default().dim(),
+            string: Style::default().dim().italic(),
+            comment: Style::default().dim().italic(),
+            number: Style::default().dim(),
+            punctuation: Style::default().dim(),
+            label: Style::default(),
+            value: Style::default().bold(),
+            shortcut: Style::default().bold(),
+            action: Style::default(),
+            header: Style::default().bold().underlined(),
+            tab: Style::default(),
+            selected_tab: Style::default().reversed(),
+            selected_row: Style::default().reversed(),
+            success: Style::default().bold(),
+            status: Style::default().bold(),
+            muted: Style::default().dim(),
+            wpm_line: Style::default().bold(),
+            raw_wpm_line: Style::default().dim(),
+            gauge: Style::default(),
+            heatmap_unused: Style::default().dim(),
+            accuracy: vec![
+                AccuracyLevel {
+                    minimum: 1.0,
+                    style: Style::default().bold(),
+                },
+                AccuracyLevel {
+                    minimum: 0.0,
+                    style: Style::default(),
+                },
+            ],
+            heatmap: [
+                Color::Rgb(90, 90, 90),
+                Color::Rgb(165, 165, 165),
+                Color::Rgb(240, 240, 240),
+            ],
+            ..Theme::default()
+        }
+    }
+
+    pub fn all_builtin() -> Vec<Theme> {
+        vec![
+            Theme::default(),
+            Theme::high_contrast(),
+            Theme::colour_blind_safe(),
+            Theme::monochrome(),
+        ]
+    }
+
+    /// Style of the live statistics and of the progress bar for the given accuracy.
+    pub fn accuracy_style(&self, accuracy: f64) -> Style {
+        self.accuracy
+            .iter()
+            .find(|level| accuracy >= level.minimum)
+            .or(self.accuracy.last())
+            .map_or(Style::default(), |level| level.style)
+    }
+
+    /// Maps a severity from 0 (good) to 1 (bad) onto the heatmap gradient. RGB colours are
+    /// blended, other colours are picked from the nearest end or middle of the gradient.
+    pub fn heatmap_color(&self, severity: f64) -> Color {
+        let severity = severity.clamp(0.0, 1.0);
+        let (from, to, ratio) = if severity < 0.5 {
+            (self.heatmap[0], self.heatmap[1], severity * 2.0)
+        } else {
+            (self.heatmap[1], self.heatmap[2], (severity - 0.5) * 2.0)
+        };
+
+        match (from, to) {
+            (Color::Rgb(r1, g1, b1), Color::Rgb(r2, g2, b2)) => {
+                let blend =
+                    |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * ratio) as u8;
+                Color::Rgb(blend(r1, r2), blend(g1, g2), blend(b1, b2))
+            }
+            _ if ratio < 0.5 => from,
+            _ => to,
+        }
+    }
+
+    /// Style of the untyped target text of code tests.
+    pub fn token_style(&self, kind: TokenKind) -> Style {
+        match kind {
+            TokenKind::Plain => self.target,
+            TokenKind::Keyword => self.keyword,
+            TokenKind::Type => self.type_name,
+            TokenKind::String => self.string,
+            TokenKind::Comment => self.comment,
+            TokenKind::Number => self.number,
+            TokenKind::Punctuation => self.punctuation,
+        }
+    }
+
+    fn element_mut(&mut self, element: &str) -> Option<&mut Style> {
+        Some(match element {
+            "target" => &mut self.target,
+            "correct" => &mut self.correct,
+            "incorrect" => &mut self.incorrect,
+            "missing" => &mut self.missing,
+            "extra" => &mut self.extra,
+            "transposed" => &mut self.transposed,
+            "ghost" => &mut self.ghost,
+            "keyword" => &mut self.keyword,
+            "type" => &mut self.type_name,
+            "string" => &mut self.string,
+            "comment" => &mut self.comment,
+            "number" => &mut self.number,
+            "punctuation" => &mut self.punctuation,
+            "label" => &mut self.label,
+            "value" => &mut self.value,
+            "shortcut" => &mut self.shortcut,
+            "action" => &mut self.action,
+            "header" => &mut self.header,
+            "tab" => &mut self.tab,
+            "selected_tab" => &mut self.selected_tab,
+            "selected_row" => &mut self.selected_row,
+            "success" => &mut self.success,
+            "status" => &mut self.status,
+            "muted" => &mut self.muted,
+            "wpm_line" => &mut self.wpm_line,
+            "raw_wpm_line" => &mut self.raw_wpm_line,
+            "gauge" => &mut self.gauge,
+            "heatmap_key" => &mut self.heatmap_key,
+            "heatmap_unused" => &mut self.heatmap_unused,
+            _ => return None,
+        })
+    }
+
+    /// Finds a theme by name (ignoring case) among `themes`, or else by file path, so that a file
+    /// in the current directory does not shadow a known theme. Files are loaded from their
+    /// canonical path, which stays valid from any other directory.
+    pub fn find(themes: &[Theme], name_or_path: &str) -> std::io::Result<Theme> {
+        if let Some(theme) = themes
+            .iter()
+            .find(|theme| theme.name.eq_ignore_ascii_case(name_or_path))
+        {
+            return Ok(theme.clone());
+        }
+
+        let path = std::path::Path::new(name_or_path);
+        if path.is_file() {
+            return Theme::load(&path.canonicalize()?);
+        }
+
+        Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!(
+                "unknown theme '{}', the available ones are: {}",
+                name_or_path,
+                themes
+                    .iter()
+                    .map(|theme| theme.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        ))
+    }
+
+    /// Directory scanned for user-supplied themes at startup.
+    pub fn user_directory() -> Option<std::path::PathBuf> {
+        dirs::config_dir().map(|config_dir| config_dir.join("terminal_typing_lab").join("themes"))
+    }
+
+    /// Loads every `.toml` file in `directory` as a theme, ordered by file name. A missing
+    /// directory is not an error, it simply contains no themes.
+    pub fn load_directory(directory: &std::path::Path) -> std::io::Result<Vec<Theme>> {
+        let entries = match std::fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+
+        let mut paths = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|extension| extension == "toml")
+            {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        Ok(paths
+            .iter()
+            .filter_map(|path| match Theme::load(path) {
+                Ok(theme) => Some(theme),
+                Err(error) => {
+                    eprintln!("warning: skipping theme {}", error);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// Loads a theme from a TOML file. The theme is named after the file unless it has a
+    /// `name`.
+    pub fn load(path: &std::path::Path) -> std::io::Result<Theme> {
+        let invalid_data = |message: String| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), message),
+            )
+        };
+
+        let contents =
+            std::fs::read_to_string(path).map_err(|error| invalid_data(error.to_string()))?;
+        let file: ThemeFile =
+            toml::from_str(&contents).map_err(|error| invalid_data(error.to_string()))?;
+
+        let mut theme = match &file.base {
+            Some(base) => Theme::all_builtin()
+                .into_iter()
+                .find(|theme| theme.name.eq_ignore_ascii_case(base))
+                .ok_or_else(|| invalid_data(format!("unknown base theme '{}'", base)))?,
+            None => Theme::default(),
+        };
+        theme.name = file.name.unwrap_or_else(|| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+        theme.path = Some(path.to_path_buf());
+
+        for (element, style) in &file.styles {
+            let style = style.to_style().map_err(invalid_data)?;
+            *theme
+                .element_mut(element)
+                .ok_or_else(|| invalid_data(format!("unknown element '{}'", element)))? = style;
+        }
+
+        if let Some(levels) = &file.accuracy {
+            let mut accuracy = Vec::with_capacity(levels.len());
+            for level in levels {
+                accuracy.push(AccuracyLevel {
+                    minimum: level.minimum,
+                    style: level.style.to_style().map_err(invalid_data)?,
+                });
+            }
+            accuracy.sort_by(|a, b| b.minimum.total_cmp(&a.minimum));
+            theme.accuracy = accuracy;
+        }
+
+        if let Some(heatmap) = &file.heatmap {
+            for (stop, color) in theme.heatmap.iter_mut().zip(heatmap) {
+                *stop = parse_color(color).map_err(invalid_data)?;
+            }
+        }
+
+        Ok(theme)
+    }
+}
@@ -1,10 +1,180 @@
-use crate::model::Model;
+use crate::{
+    action::Action,
+    alignment::ErrorKind,
+    corpus::{Corpus, CorpusKind},
+    history::format_timestamp,
+    keyboard::Key,
+    model::{HeatmapMetric, Model, ResultsTab, Screen, Test},
+    race::Racer,
+};
 use unicode_segmentation::UnicodeSegmentation;
 
 use ratatui::{prelude::*, widgets::*};
 
+/// Number of rows of the text panel of the test screen.
+const TEXT_ROWS: u16 = 5;
+
+/// Width of the widest live statistic, "Accuracy: 100.00%", which the test settings next to them
+/// give way to on narrow screens.
+const STATISTICS_WIDTH: u16 = 17;
+
+/// Width of the names next to the progress bars of the other players of a race.
+const RACER_NAME_WIDTH: u16 = 12;
+
+/// Width of the speeds after the progress bars of the other players of a race.
+const RACER_WPM_WIDTH: u16 = 8;
+
 impl Model {
     pub fn view(&self, frame: &mut ratatui::Frame) {
+        match self.screen {
+            Screen::Test => self.view_test(frame),
+            Screen::Results => self.view_results(frame),
+            Screen::History => self.view_history(frame),
+            Screen::Heatmap => self.view_heatmap(frame),
+            Screen::Replay => self.view_replay(frame),
+            Screen::Race => self.view_race(frame),
+        }
+    }
+
+    fn view_test(&self, frame: &mut ratatui::Frame) {
+        let theme = &self.config.theme;
+
+        let info = vec![
+            Line::from(vec![
+                Span::styled("Corpus: ", theme.label),
+                Span::styled(self.test_corpus.as_str(), theme.value),
+            ]),
+            Line::from(vec![
+                Span::styled("Sampling: ", theme.label),
+                Span::styled(self.config.sampling_method.name(), theme.value),
+            ]),
+            Line::from(vec![
+                Span::styled("Input: ", theme.label),
+                Span::styled(
+                    if self.config.word_by_word {
+                        "word by word"
+                    } else {
+                        "continuous"
+                    },
+                    theme.value,
+                ),
+            ]),
+            Line::from(vec![
+                Span::styled("Errors: ", theme.label),
+                Span::styled(self.config.strictness.name(), theme.value),
+            ]),
+            match self.current_test.time_limit {
+                None => Line::from(vec![
+                    Span::styled("Test Length: ", theme.label),
+                    Span::styled(
+                        match self.test_corpus().kind {
+                            CorpusKind::Code => format!("{} lines", self.test_length),
+                            CorpusKind::Words | CorpusKind::Prose => self.test_length.to_string(),
+                        },
+                        theme.value,
+                    ),
+                ]),
+                Some(time_limit) => Line::from(vec![
+                    Span::styled("Test Duration: ", theme.label),
+                    Span::styled(format!("{}s", time_limit.as_secs()), theme.value),
+                ]),
+            },
+        ];
+
+        self.view_typing(
+            frame,
+            &self.current_test,
+            self.displayed_caret,
+            Some(self.test_corpus()),
+            Screen::Test,
+            info,
+        );
+    }
+
+    fn view_replay(&self, frame: &mut ratatui::Frame) {
+        let Some(replay) = &self.replay else {
+            return;
+        };
+        let theme = &self.config.theme;
+
+        let state = if replay.is_finished() {
+            "finished"
+        } else if replay.is_paused {
+            "paused"
+        } else {
+            "playing"
+        };
+        let info = vec![
+            Line::from(vec![
+                Span::styled("Replay: ", theme.label),
+                Span::styled(replay.corpus.as_str(), theme.value),
+            ]),
+            Line::from(vec![
+                Span::styled("State: ", theme.label),
+                Span::styled(state, theme.value),
+            ]),
+            Line::from(vec![
+                Span::styled("Speed: ", theme.label),
+                Span::styled(format!("{}x", replay.speed()), theme.value),
+            ]),
+            Line::from(vec![
+                Span::styled("Time: ", theme.label),
+                Span::styled(
+                    format!(
+                        "{:.1}s / {:.1}s",
+                        replay.position.as_secs_f64(),
+                        replay.duration.as_secs_f64()
+                    ),
+                    theme.value,
+                ),
+            ]),
+            Line::from(vec![
+                Span::styled("Keystrokes: ", theme.label),
+                Span::styled(
+                    format!("{} / {}", replay.played, replay.input_count()),
+                    theme.value,
+                ),
+            ]),
+        ];
+
+        self.view_typing(
+            frame,
+            &replay.test,
+            replay.test.caret_index(),
+            self.corpora
+                .iter()
+                .find(|corpus| corpus.name == replay.corpus),
+            Screen::Replay,
+            info,
+        );
+    }
+
+    /// The screen a test is typed on, which replays are shown on too. `info` describes the test
+    /// below the text.
+    fn view_typing(
+        &self,
+        frame: &mut ratatui::Frame,
+        test: &Test,
+        displayed_caret: usize,
+        corpus: Option<&Corpus>,
+        screen: Screen,
+        info: Vec<Line>,
+    ) {
+        let theme = &self.config.theme;
+
+        // Help spans the width of the screen above the test, tall enough for every command
+        // unless that would leave no room for the text, settings and progress bar.
+        let help = self.help_lines(screen);
+        let help_width = frame.size().width.saturating_sub(2);
+        let help_height = std::cmp::min(
+            wrapped_height(&help, help_width),
+            frame.size().height.saturating_sub(TEXT_ROWS + 1 + 5 + 1),
+        );
+        let screen_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(help_height), Constraint::Fill(1)])
+            .split(frame.size());
+
         let main_layout = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([
@@ -12,244 +182,1035 @@ impl Model {
                 Constraint::Percentage(50),
                 Constraint::Percentage(25),
             ])
-            .split(frame.size());
+            .split(screen_layout[1]);
 
         let middle_layouts = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Fill(5),
                 Constraint::Fill(1),
+                Constraint::Length(TEXT_ROWS + 1),
+                // Test settings and live statistics, one per line.
+                Constraint::Length(5),
                 Constraint::Fill(1),
-                Constraint::Fill(1),
-                Constraint::Fill(5),
             ])
             .split(main_layout[1]);
+        let info_width = info.iter().map(Line::width).max().unwrap_or(0);
         let middle_lower_layouts = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints([Constraint::Fill(1), Constraint::Fill(1)])
-            .split(middle_layouts[3]);
-
-        let middle_upper_layouts = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([Constraint::Fill(1), Constraint::Fill(1)])
-            .split(middle_layouts[0]);
-
-        let middle_upper_left = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(20), Constraint::Percentage(80)])
-            .split(middle_upper_layouts[0])[1];
-
-        let middle_upper_right = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(20), Constraint::Percentage(80)])
-            .split(middle_upper_layouts[1])[1];
+            .spacing(1)
+            .constraints([
+                Constraint::Length(info_width as u16),
+                Constraint::Min(STATISTICS_WIDTH),
+            ])
+            .split(middle_layouts[2]);
 
         if self.config.show_frame_statistics {
             let average_frametime = self.frame_statistics.average_frame_duration.as_secs_f64();
             let average_fps = 1.0 / average_frametime;
+            let average_render_time = self.frame_statistics.average_render_duration.as_secs_f64();
 
             frame.render_widget(
-                Paragraph::new(format!(
-                    "Frametime {:.0}ms ({:.0}FPS)",
-                    average_frametime * 1_000.0,
-                    average_fps
-                )),
-                middle_upper_right,
+                Paragraph::new(vec![
+                    Line::from(format!(
+                        "Frametime {:.0}ms ({:.1}FPS)",
+                        average_frametime * 1_000.0,
+                        average_fps
+                    )),
+                    Line::from(format!(
+                        "Render time {:.1}ms",
+                        average_render_time * 1_000.0
+                    )),
+                ]),
+                main_layout[2],
             );
         }
 
         {
-            let target_char_style = Style::default().fg(Color::DarkGray);
-            let correct_char_style = Style::default().fg(Color::LightGreen);
-            let incorrect_char_style = Style::default().fg(Color::LightRed).underlined();
-
-            let mut spans = Vec::new();
-
-            for (target, current) in std::iter::zip(
-                self.current_test.target_text.graphemes(true),
-                self.current_test.current_text.graphemes(true),
-            ) {
-                if target == current {
-                    spans.push(Span::styled(current.to_string(), correct_char_style));
-                } else {
-                    spans.push(Span::styled(current.to_string(), incorrect_char_style));
+            let target: Vec<&str> = test.target_text.graphemes(true).collect();
+            let typed: Vec<&str> = test.current_text.graphemes(true).collect();
+            let alignment = test.alignment();
+
+            let token_kinds = corpus
+                .filter(|corpus| corpus.kind == CorpusKind::Code)
+                .map(|corpus| test.token_kinds(corpus.language.as_deref()));
+
+            let mut graphemes: Vec<Span> = Vec::with_capacity(target.len());
+
+            // The ghost is shown on the grapheme of the target it is about to type, which may be
+            // in the part already typed.
+            let ghost_position = self
+                .ghost
+                .as_ref()
+                .filter(|_| screen == Screen::Test)
+                .map(|ghost| ghost.test.target_position());
+            let mut ghost_index = None;
+
+            for step in &alignment.steps {
+                let (step_graphemes, style) = match step.error {
+                    None => (&typed[step.typed.clone()], theme.correct),
+                    Some(ErrorKind::Substitution) => (&typed[step.typed.clone()], theme.incorrect),
+                    Some(ErrorKind::Insertion) => (&typed[step.typed.clone()], theme.extra),
+                    Some(ErrorKind::Omission) => (&target[step.target.clone()], theme.missing),
+                    Some(ErrorKind::Transposition) => {
+                        (&typed[step.typed.clone()], theme.transposed)
+                    }
+                };
+                if let Some(ghost_position) = ghost_position {
+                    if ghost_index.is_none() && step.target.end > ghost_position {
+                        ghost_index = Some(
+                            graphemes.len()
+                                + std::cmp::min(
+                                    ghost_position.saturating_sub(step.target.start),
+                                    step_graphemes.len().saturating_sub(1),
+                                ),
+                        );
+                    }
+                }
+                for grapheme in step_graphemes {
+                    graphemes.push(Span::styled(*grapheme, style));
                 }
             }
 
-            for remaining in self
-                .current_test
-                .target_text
-                .graphemes(true)
-                .skip(self.current_test.current_text_grapheme_count)
-            {
-                spans.push(Span::styled(remaining.to_string(), target_char_style));
+            let caret = graphemes.len();
+            let ghost_index = ghost_index.or_else(|| {
+                ghost_position.map(|ghost_position| {
+                    caret + ghost_position.saturating_sub(alignment.target_end())
+                })
+            });
+
+            for (position, remaining) in target.iter().enumerate().skip(alignment.target_end()) {
+                let style = match &token_kinds {
+                    Some(token_kinds) => theme.token_style(token_kinds[position]),
+                    None => theme.target,
+                };
+                graphemes.push(Span::styled(*remaining, style));
+            }
+
+            if let Some(grapheme) = ghost_index.and_then(|index| graphemes.get_mut(index)) {
+                grapheme.style = grapheme.style.patch(theme.ghost);
             }
 
+            // The caret stays on the second row, so that the typist always sees the row they
+            // just finished and the rows coming up, and the text scrolls one row at a time.
+            let text_area = middle_layouts[1];
+            let rows = wrap_rows(&graphemes, usize::from(text_area.width));
+            let caret_row = rows
+                .iter()
+                .position(|row| row.contains(&caret))
+                .unwrap_or(rows.len().saturating_sub(1));
+            let first_row = std::cmp::min(
+                caret_row.saturating_sub(1),
+                rows.len().saturating_sub(usize::from(TEXT_ROWS)),
+            );
+
             frame.render_widget(
-                Paragraph::new(Line::from(spans)).wrap(Wrap { trim: false }),
-                middle_layouts[2],
+                Paragraph::new(
+                    rows.iter()
+                        .skip(first_row)
+                        .take(usize::from(TEXT_ROWS))
+                        .map(|row| {
+                            Line::from(
+                                graphemes[row.clone()]
+                                    .iter()
+                                    .map(|grapheme| match grapheme.content.as_ref() {
+                                        // Newlines are shown so that they can be seen when
+                                        // they are mistyped.
+                                        "\n" => Span::styled("⏎", grapheme.style),
+                                        _ => grapheme.clone(),
+                                    })
+                                    .collect::<Vec<_>>(),
+                            )
+                        })
+                        .collect::<Vec<_>>(),
+                ),
+                text_area,
             );
+
+            // The terminal cursor is the caret, which also lets screen readers and input methods
+            // follow the typist.
+            if !test.is_finished() {
+                let displayed_caret = std::cmp::min(displayed_caret, graphemes.len());
+                let displayed_caret_row = rows
+                    .iter()
+                    .position(|row| row.contains(&displayed_caret))
+                    .unwrap_or(rows.len().saturating_sub(1));
+                let visible_rows = first_row..first_row + usize::from(TEXT_ROWS);
+                if let (true, Some(row)) = (
+                    visible_rows.contains(&displayed_caret_row),
+                    rows.get(displayed_caret_row),
+                ) {
+                    let column: usize = graphemes[row.start..displayed_caret]
+                        .iter()
+                        .map(Span::width)
+                        .sum();
+                    let column =
+                        std::cmp::min(column, usize::from(text_area.width.saturating_sub(1)));
+                    frame.set_cursor(
+                        text_area.x + column as u16,
+                        text_area.y + (displayed_caret_row - first_row) as u16,
+                    );
+                }
+            }
         }
 
-        {
-            let shortcut_style = Style::default().fg(Color::Yellow);
-            let action_style = Style::default().fg(Color::Blue);
+        frame.render_widget(
+            Paragraph::new(help).wrap(Wrap { trim: true }),
+            screen_layout[0].inner(&Margin {
+                horizontal: 1,
+                vertical: 0,
+            }),
+        );
+
+        frame.render_widget(Paragraph::new(info), middle_lower_layouts[0]);
+
+        let progress_area = middle_layouts[3].clamp(Rect::new(
+            middle_layouts[3].x,
+            middle_layouts[3].y,
+            middle_layouts[3].width,
+            1,
+        ));
 
+        if let Some(remaining_time) = test.remaining_time() {
             frame.render_widget(
-                Paragraph::new(vec![
-                    Line::from(vec![
-                        Span::styled("Backspace", shortcut_style),
-                        Span::from(", or "),
-                        Span::styled("Control-h", shortcut_style),
-                        Span::from(" - "),
-                        Span::styled("delete character", action_style),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Control-Backspace", shortcut_style),
-                        Span::from(", or "),
-                        Span::styled("Control-w", shortcut_style),
-                        Span::from(" - "),
-                        Span::styled("delete word", action_style),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Tab", shortcut_style),
-                        Span::from(" - "),
-                        Span::styled("restart", action_style),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Enter", shortcut_style),
-                        Span::from(" - "),
-                        Span::styled("next test", action_style),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Left", shortcut_style),
-                        Span::from(" - "),
-                        Span::styled("previous corpus", action_style),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Right", shortcut_style),
-                        Span::from(" - "),
-                        Span::styled("next corpus", action_style),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Up", shortcut_style),
-                        Span::from(" - "),
-                        Span::styled("increase test length", action_style),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Down", shortcut_style),
-                        Span::from(" - "),
-                        Span::styled("decrease test length", action_style),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Control-l", shortcut_style),
-                        Span::from(" - "),
-                        Span::styled("toggle live typing statistics", action_style),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Control-s", shortcut_style),
-                        Span::from(" - "),
-                        Span::styled("toggle frame statistics", action_style),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Esc", shortcut_style),
-                        Span::from(", "),
-                        Span::styled("Control-c", shortcut_style),
-                        Span::from(", or "),
-                        Span::styled("Control-q", shortcut_style),
-                        Span::from(" - "),
-                        Span::styled("quit", action_style),
-                    ]),
-                ]),
-                middle_upper_left,
+                Paragraph::new(Line::from(vec![
+                    Span::styled("Time left: ", theme.label),
+                    Span::styled(
+                        format!("{}s", remaining_time.as_secs_f64().ceil()),
+                        theme.value,
+                    ),
+                ])),
+                progress_area,
             );
         }
 
-        frame.render_widget(
-            Paragraph::new(vec![
-                Line::from(vec![
-                    Span::styled("Corpus: ", Style::default().fg(Color::White)),
-                    Span::styled(
-                        match self.config.corpus.id {
-                            crate::corpus::EmbeddedCorpora::English200Words => {
-                                "English Top 200 Words"
-                            }
-                            crate::corpus::EmbeddedCorpora::English200Bigrams => {
-                                "English Top 200 Bigrams"
-                            }
-                            crate::corpus::EmbeddedCorpora::English200Trigrams => {
-                                "English Top 200 Trigrams"
+        if self.config.show_live_typing_statistics || test.is_finished() {
+            let accuracy_style = theme.accuracy_style(test.accuracy());
+
+            if test.time_limit.is_none() {
+                frame.render_widget(
+                    Gauge::default()
+                        .gauge_style(theme.gauge.fg(accuracy_style.fg.unwrap_or(Color::Reset)))
+                        .use_unicode(true)
+                        .ratio(test.completion()),
+                    progress_area,
+                );
+            }
+
+            let mut lines = vec![
+                Line::from(vec![Span::styled(
+                    format!("Accuracy: {:.2}%", test.accuracy() * 100.0),
+                    accuracy_style,
+                )]),
+                Line::from(vec![Span::styled(
+                    format!("WPM: {:.0}", test.wpm()),
+                    accuracy_style,
+                )]),
+                Line::from(vec![Span::styled(
+                    format!("Raw WPM: {:.0}", test.raw_wpm()),
+                    accuracy_style,
+                )]),
+                Line::from(vec![Span::styled(
+                    format!("Duration: {}s", test.duration().as_secs()),
+                    accuracy_style,
+                )]),
+            ];
+            if screen == Screen::Test && self.config.ghost {
+                let ghost = match &self.ghost {
+                    Some(ghost) => {
+                        let ghost_position = ghost.test.target_position();
+                        let position = test.target_position();
+                        match ghost_position.cmp(&position) {
+                            std::cmp::Ordering::Greater => {
+                                format!("{} ahead", ghost_position - position)
                             }
-                            crate::corpus::EmbeddedCorpora::English200Tetragrams => {
-                                "English Top 200 Tetragrams"
+                            std::cmp::Ordering::Less => {
+                                format!("{} behind", position - ghost_position)
                             }
-                        },
-                        Style::default().fg(Color::Cyan),
+                            std::cmp::Ordering::Equal => "even".to_string(),
+                        }
+                    }
+                    None => "no past run".to_string(),
+                };
+                lines.push(Line::from(vec![
+                    Span::styled("Ghost: ", theme.label),
+                    Span::styled(ghost, theme.value),
+                ]));
+            }
+            frame.render_widget(Paragraph::new(lines), middle_lower_layouts[1]);
+        }
+
+        // The other players of a race follow the progress of the typist, one per row.
+        let racers: Vec<&Racer> = match &self.race {
+            Some(race) if screen == Screen::Test && race.is_racing => race
+                .leaderboard()
+                .into_iter()
+                .filter(|racer| racer.progress.is_some() && Some(racer.id) != race.id)
+                .collect(),
+            _ => Vec::new(),
+        };
+        let racers_area = middle_layouts[3];
+        for (row, racer) in (1..racers_area.height).zip(&racers) {
+            let Some(progress) = racer.progress else {
+                continue;
+            };
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Length(RACER_NAME_WIDTH),
+                    Constraint::Fill(1),
+                    Constraint::Length(RACER_WPM_WIDTH),
+                ])
+                .split(Rect::new(
+                    racers_area.x,
+                    racers_area.y + row,
+                    racers_area.width,
+                    1,
+                ));
+            frame.render_widget(
+                Paragraph::new(Span::styled(racer.name.as_str(), theme.label)),
+                columns[0],
+            );
+            frame.render_widget(
+                Gauge::default()
+                    .gauge_style(
+                        theme.gauge.fg(theme
+                            .accuracy_style(progress.accuracy)
+                            .fg
+                            .unwrap_or(Color::Reset)),
+                    )
+                    .use_unicode(true)
+                    .ratio(progress.completion.clamp(0.0, 1.0)),
+                columns[1],
+            );
+            frame.render_widget(
+                Paragraph::new(Span::styled(
+                    format!("{:.0} WPM", progress.wpm),
+                    theme.value,
+                ))
+                .alignment(Alignment::Right),
+                columns[2],
+            );
+        }
+
+        if let Some(status_message) = &self.status_message {
+            let status_area = middle_layouts[3];
+            let offset = 2 + racers.len() as u16;
+            frame.render_widget(
+                Paragraph::new(status_message.as_str())
+                    .style(theme.status)
+                    .wrap(Wrap { trim: true }),
+                Rect::new(
+                    status_area.x,
+                    status_area.y.saturating_add(offset),
+                    status_area.width,
+                    status_area.height.saturating_sub(offset),
+                ),
+            );
+        }
+    }
+
+    fn view_results(&self, frame: &mut ratatui::Frame) {
+        let theme = &self.config.theme;
+
+        let footer = self.footer_lines(Screen::Results);
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(2),
+                Constraint::Fill(1),
+                Constraint::Length(footer.len() as u16),
+            ])
+            .margin(1)
+            .split(frame.size());
+
+        frame.render_widget(
+            Tabs::new(ResultsTab::ALL.iter().map(|tab| tab.name()))
+                .select(
+                    ResultsTab::ALL
+                        .iter()
+                        .position(|&tab| tab == self.results_tab)
+                        .unwrap_or(0),
+                )
+                .style(theme.tab)
+                .highlight_style(theme.selected_tab),
+            layout[0],
+        );
+
+        let header_style = theme.header;
+        let test = &self.current_test;
+
+        match self.results_tab {
+            ResultsTab::Summary => {
+                let summary_layout = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(9), Constraint::Fill(1)])
+                    .split(layout[1]);
+
+                let label_style = theme.label;
+                let value_style = theme.value;
+                let statistic = |label: &'static str, value: String| {
+                    Line::from(vec![
+                        Span::styled(label, label_style),
+                        Span::styled(value, value_style),
+                    ])
+                };
+
+                let personal_best = match self.previous_personal_best {
+                    None => Line::from(Span::styled(
+                        "First test with this corpus and length",
+                        label_style,
+                    )),
+                    Some(best) if test.wpm() > best => Line::from(vec![
+                        Span::styled("New personal best! ", theme.success),
+                        Span::styled(format!("(previously {:.0} WPM)", best), label_style),
+                    ]),
+                    Some(best) => statistic(
+                        "Personal best: ",
+                        format!("{:.0} WPM ({:+.0})", best, test.wpm() - best),
                     ),
-                ]),
-                Line::from(vec![
-                    Span::styled("Test Length: ", Style::default().fg(Color::White)),
-                    Span::styled(
-                        self.config.test_length.to_string(),
-                        Style::default().fg(Color::Cyan),
+                };
+
+                frame.render_widget(
+                    Paragraph::new(vec![
+                        statistic("WPM: ", format!("{:.0}", test.wpm())),
+                        statistic("Raw WPM: ", format!("{:.0}", test.raw_wpm())),
+                        statistic("Accuracy: ", format!("{:.2}%", test.accuracy() * 100.0)),
+                        statistic(
+                            "Consistency: ",
+                            self.results
+                                .consistency
+                                .map_or("-".to_string(), |consistency| {
+                                    format!("{:.0}%", consistency * 100.0)
+                                }),
+                        ),
+                        statistic(
+                            "Duration: ",
+                            format!("{:.1}s", test.duration().as_secs_f64()),
+                        ),
+                        statistic(
+                            "Characters: ",
+                            format!(
+                                "{} correct out of {} typed",
+                                test.correct_graphemes(),
+                                test.typed_graphemes()
+                            ),
+                        ),
+                        personal_best,
+                        statistic(
+                            "Test code: ",
+                            self.test_code()
+                                .map_or("-".to_string(), |code| code.to_string()),
+                        ),
+                    ]),
+                    summary_layout[0],
+                );
+
+                let samples = &self.results.speed_over_time;
+                let wpm_points: Vec<(f64, f64)> = samples
+                    .iter()
+                    .map(|sample| (sample.second as f64 + 1.0, sample.wpm))
+                    .collect();
+                let raw_wpm_points: Vec<(f64, f64)> = samples
+                    .iter()
+                    .map(|sample| (sample.second as f64 + 1.0, sample.raw_wpm))
+                    .collect();
+                let max_wpm = samples
+                    .iter()
+                    .map(|sample| sample.raw_wpm)
+                    .fold(0.0, f64::max)
+                    .max(10.0);
+                let seconds = std::cmp::max(samples.len(), 1) as f64;
+
+                frame.render_widget(
+                    Chart::new(vec![
+                        Dataset::default()
+                            .name("Raw WPM")
+                            .marker(symbols::Marker::Braille)
+                            .graph_type(GraphType::Line)
+                            .style(theme.raw_wpm_line)
+                            .data(&raw_wpm_points),
+                        Dataset::default()
+                            .name("WPM")
+                            .marker(symbols::Marker::Braille)
+                            .graph_type(GraphType::Line)
+                            .style(theme.wpm_line)
+                            .data(&wpm_points),
+                    ])
+                    .x_axis(
+                        Axis::default()
+                            .title("Seconds")
+                            .style(label_style)
+                            .bounds([1.0, seconds])
+                            .labels(vec![
+                                Span::from("1"),
+                                Span::from(format!("{}", samples.len())),
+                            ]),
+                    )
+                    .y_axis(
+                        Axis::default()
+                            .title("WPM")
+                            .style(label_style)
+                            .bounds([0.0, max_wpm])
+                            .labels(vec![Span::from("0"), Span::from(format!("{:.0}", max_wpm))]),
                     ),
-                ]),
-            ]),
-            middle_lower_layouts[0],
+                    summary_layout[1],
+                );
+            }
+            ResultsTab::Errors => {
+                let errors = &self.results.errors_by_character;
+                let error_counts = test.alignment().error_counts();
+                if errors.is_empty() && error_counts.total() == 0 {
+                    frame.render_widget(
+                        Paragraph::new("No mistakes, well done!").style(theme.success),
+                        layout[1],
+                    );
+                } else {
+                    let errors_layout = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Length(5), Constraint::Fill(1)])
+                        .split(layout[1]);
+
+                    frame.render_widget(
+                        Table::new(
+                            [
+                                ("Substitutions", error_counts.substitutions),
+                                ("Insertions", error_counts.insertions),
+                                ("Omissions", error_counts.omissions),
+                                ("Transpositions", error_counts.transpositions),
+                            ]
+                            .map(|(kind, count)| {
+                                Row::new(vec![kind.to_string(), count.to_string()])
+                            }),
+                            [Constraint::Length(14), Constraint::Length(6)],
+                        )
+                        .header(Row::new(vec!["Kind", "Errors"]).style(header_style)),
+                        errors_layout[0],
+                    );
+
+                    frame.render_widget(
+                        Table::new(
+                            errors.iter().map(|(grapheme, count)| {
+                                Row::new(vec![
+                                    if grapheme == " " {
+                                        "␣".to_string()
+                                    } else {
+                                        grapheme.clone()
+                                    },
+                                    count.to_string(),
+                                ])
+                            }),
+                            [Constraint::Length(9), Constraint::Length(6)],
+                        )
+                        .header(Row::new(vec!["Character", "Errors"]).style(header_style)),
+                        errors_layout[1],
+                    );
+                }
+            }
+            ResultsTab::Words => {
+                frame.render_widget(
+                    Table::new(
+                        self.results.slowest_words.iter().map(|timing| {
+                            Row::new(vec![
+                                timing.word.clone(),
+                                format!("{:.2}s", timing.duration.as_secs_f64()),
+                                format!("{:.0}", timing.wpm),
+                            ])
+                        }),
+                        [
+                            Constraint::Fill(1),
+                            Constraint::Length(8),
+                            Constraint::Length(5),
+                        ],
+                    )
+                    .header(Row::new(vec!["Word", "Time", "WPM"]).style(header_style)),
+                    layout[1],
+                );
+            }
+        }
+
+        frame.render_widget(Paragraph::new(footer), layout[2]);
+    }
+
+    fn view_heatmap(&self, frame: &mut ratatui::Frame) {
+        let theme = &self.config.theme;
+
+        const KEY_WIDTH: usize = 6;
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(2),
+                Constraint::Fill(1),
+                Constraint::Length(3),
+            ])
+            .margin(1)
+            .split(frame.size());
+
+        let label_style = theme.label;
+        let value_style = theme.value;
+
+        frame.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::styled("Layout: ", label_style),
+                Span::styled(self.config.keyboard_layout.name(), value_style),
+                Span::styled("   Showing: ", label_style),
+                Span::styled(
+                    match self.heatmap_metric {
+                        HeatmapMetric::ErrorRate => "error rate (%)",
+                        HeatmapMetric::Latency => "mean latency (ms)",
+                    },
+                    value_style,
+                ),
+            ])),
+            layout[0],
         );
 
-        if self.config.show_live_typing_statistics || self.current_test.is_finished() {
-            let accuracy_style = {
-                let accuracy = self.current_test.accuracy();
-                if accuracy >= 1.0 {
-                    Style::default().fg(Color::LightGreen)
-                } else if accuracy >= 0.95 {
-                    Style::default().fg(Color::Green)
-                } else if accuracy >= 0.75 {
-                    Style::default().fg(Color::Yellow)
-                } else {
-                    Style::default().fg(Color::Red)
+        let key_statistics = |key: Key| {
+            let mut statistics = crate::statistics::KeyStatistics::default();
+            let mut add = |grapheme: String| {
+                if let Some(key_statistics) = self.key_statistics.get(&grapheme) {
+                    statistics.add(key_statistics);
                 }
             };
+            add(key.normal.to_string());
+            if key.shifted != key.normal {
+                add(key.shifted.to_string());
+            }
+            statistics
+        };
+
+        let rows = self.config.keyboard_layout.rows();
+        let space = Key {
+            normal: ' ',
+            shifted: ' ',
+        };
+
+        // Latencies are coloured relative to the fastest and slowest keys of the layout.
+        let latencies: Vec<f64> = rows
+            .iter()
+            .flat_map(|row| row.keys.iter().copied())
+            .chain([space])
+            .filter_map(|key| key_statistics(key).mean_latency())
+            .map(|latency| latency.as_secs_f64())
+            .collect();
+        let fastest = latencies.iter().copied().fold(f64::INFINITY, f64::min);
+        let slowest = latencies.iter().copied().fold(0.0, f64::max);
+
+        let key_cell = |label: String, key: Key, width: usize| -> (Span, Span) {
+            let statistics = key_statistics(key);
+            let (severity, value) = match self.heatmap_metric {
+                HeatmapMetric::ErrorRate => match statistics.error_rate() {
+                    Some(error_rate) => (
+                        Some((error_rate / 0.15).min(1.0)),
+                        format!("{:.0}", error_rate * 100.0),
+                    ),
+                    None => (None, String::new()),
+                },
+                HeatmapMetric::Latency => match statistics.mean_latency() {
+                    Some(latency) => (
+                        Some(if slowest > fastest {
+                            (latency.as_secs_f64() - fastest) / (slowest - fastest)
+                        } else {
+                            0.0
+                        }),
+                        format!("{}", latency.as_millis()),
+                    ),
+                    None => (None, String::new()),
+                },
+            };
+
+            let style = match severity {
+                Some(severity) => theme.heatmap_key.bg(theme.heatmap_color(severity)),
+                None => theme.heatmap_unused,
+            };
+
+            (
+                Span::styled(format!("{:^width$}", label, width = width - 1), style),
+                Span::styled(format!("{:^width$}", value, width = width - 1), style),
+            )
+        };
+
+        let mut lines = Vec::new();
+        for row in &rows {
+            let indent = " ".repeat((row.offset * KEY_WIDTH as f64).round() as usize);
+            let mut labels = vec![Span::from(indent.clone())];
+            let mut values = vec![Span::from(indent)];
+            for &key in &row.keys {
+                let (label, value) = key_cell(key.normal.to_string(), key, KEY_WIDTH);
+                labels.extend([label, Span::from(" ")]);
+                values.extend([value, Span::from(" ")]);
+            }
+            lines.extend([Line::from(labels), Line::from(values), Line::default()]);
+        }
+
+        let space_indent = " ".repeat(4 * KEY_WIDTH);
+        let (label, value) = key_cell("space".to_string(), space, 6 * KEY_WIDTH);
+        lines.extend([
+            Line::from(vec![Span::from(space_indent.clone()), label]),
+            Line::from(vec![Span::from(space_indent), value]),
+        ]);
+
+        let keyboard_width = 16 * KEY_WIDTH as u16;
+        let keyboard_area = Rect {
+            x: layout[1].x + layout[1].width.saturating_sub(keyboard_width) / 2,
+            width: std::cmp::min(keyboard_width, layout[1].width),
+            ..layout[1]
+        };
+        frame.render_widget(Paragraph::new(lines), keyboard_area);
+
+        frame.render_widget(Paragraph::new(self.help_lines(Screen::Heatmap)), layout[2]);
+    }
+
+    fn view_history(&self, frame: &mut ratatui::Frame) {
+        let theme = &self.config.theme;
+
+        let footer = self.footer_lines(Screen::History);
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(2),
+                Constraint::Fill(1),
+                Constraint::Length(footer.len() as u16),
+            ])
+            .margin(1)
+            .split(frame.size());
+
+        let label_style = theme.label;
+        let value_style = theme.value;
 
+        frame.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::styled("Sort: ", label_style),
+                Span::styled(
+                    format!(
+                        "{} {}",
+                        self.history_browser.sort.name(),
+                        if self.history_browser.descending {
+                            "↓"
+                        } else {
+                            "↑"
+                        }
+                    ),
+                    value_style,
+                ),
+                Span::styled("   Corpus: ", label_style),
+                Span::styled(
+                    self.history_browser
+                        .corpus_filter
+                        .as_deref()
+                        .unwrap_or("All"),
+                    value_style,
+                ),
+            ])),
+            layout[0],
+        );
+
+        let rows = self.history_browser.rows(&self.history);
+
+        if rows.is_empty() {
             frame.render_widget(
-                Gauge::default()
-                    .gauge_style(
-                        Style::default()
-                            .fg(accuracy_style.fg.unwrap())
-                            .bg(Color::Black),
-                    )
-                    .use_unicode(true)
-                    .ratio(self.current_test.completion()),
-                middle_layouts[4].clamp(Rect::new(
-                    middle_layouts[4].x,
-                    middle_layouts[4].y,
-                    middle_layouts[4].width,
-                    1,
-                )),
+                Paragraph::new("No finished tests yet").style(theme.muted),
+                layout[1],
             );
+        } else {
+            let header_style = theme.header;
+            let table = Table::new(
+                rows.iter().map(|record| {
+                    Row::new(vec![
+                        format_timestamp(record.timestamp),
+                        record.corpus.clone(),
+                        record.length_label(),
+                        format!("{:.0}", record.wpm),
+                        format!("{:.0}", record.raw_wpm),
+                        format!("{:.2}%", record.accuracy * 100.0),
+                        format!("{:.1}s", record.duration.as_secs_f64()),
+                    ])
+                }),
+                [
+                    Constraint::Length(16),
+                    Constraint::Fill(1),
+                    Constraint::Length(6),
+                    Constraint::Length(5),
+                    Constraint::Length(7),
+                    Constraint::Length(9),
+                    Constraint::Length(9),
+                ],
+            )
+            .header(
+                Row::new(vec![
+                    "Date", "Corpus", "Length", "WPM", "Raw WPM", "Accuracy", "Duration",
+                ])
+                .style(header_style),
+            )
+            .highlight_style(theme.selected_row);
+
+            let mut table_state = TableState::default().with_selected(Some(std::cmp::min(
+                self.history_browser.selected,
+                rows.len() - 1,
+            )));
+            frame.render_stateful_widget(table, layout[1], &mut table_state);
+        }
+
+        frame.render_widget(Paragraph::new(footer), layout[2]);
+    }
+
+    fn view_race(&self, frame: &mut ratatui::Frame) {
+        let Some(race) = &self.race else {
+            return;
+        };
+        let theme = &self.config.theme;
 
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(2),
+                Constraint::Fill(1),
+                Constraint::Length(4),
+            ])
+            .margin(1)
+            .split(frame.size());
+
+        let state = if !race.is_connected {
+            "disconnected"
+        } else if !race.is_racing {
+            "waiting in the lobby"
+        } else if race.is_race_over() {
+            "race over"
+        } else {
+            "racing"
+        };
+        frame.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::styled("Race: ", theme.label),
+                Span::styled(state, theme.value),
+                Span::styled("   Players: ", theme.label),
+                Span::styled(race.racers.len().to_string(), theme.value),
+            ])),
+            layout[0],
+        );
+
+        let racers = race.leaderboard();
+        if racers.is_empty() {
             frame.render_widget(
-                Paragraph::new(vec![
-                    Line::from(vec![Span::styled(
-                        format!("Accuracy: {:.2}%", self.current_test.accuracy() * 100.0),
-                        accuracy_style,
-                    )]),
-                    Line::from(vec![Span::styled(
-                        format!("WPM: {:.0}", self.current_test.wpm()),
-                        accuracy_style,
-                    )]),
-                    Line::from(vec![Span::styled(
-                        format!("Raw WPM: {:.0}", self.current_test.raw_wpm()),
-                        accuracy_style,
-                    )]),
-                    Line::from(vec![Span::styled(
-                        format!("Duration: {}s", self.current_test.duration().as_secs()),
-                        accuracy_style,
-                    )]),
-                ]),
-                middle_lower_layouts[1],
+                Paragraph::new("Nobody has joined yet").style(theme.muted),
+                layout[1],
+            );
+        } else {
+            let table = Table::new(
+                racers.iter().enumerate().map(|(index, racer)| {
+                    let mut name = racer.name.clone();
+                    if Some(racer.id) == race.host {
+                        name.push_str(" (host)");
+                    }
+                    let row = match racer.progress {
+                        Some(progress) => Row::new(vec![
+                            (index + 1).to_string(),
+                            name,
+                            format!("{:.0}%", progress.completion * 100.0),
+                            format!("{:.0}", progress.wpm),
+                            format!("{:.2}%", progress.accuracy * 100.0),
+                            if progress.is_finished {
+                                "finished"
+                            } else {
+                                "typing"
+                            }
+                            .to_string(),
+                        ]),
+                        None => Row::new(vec![
+                            "-".to_string(),
+                            name,
+                            String::new(),
+                            String::new(),
+                            String::new(),
+                            "next race".to_string(),
+                        ]),
+                    };
+                    if Some(racer.id) == race.id {
+                        row.style(theme.selected_row)
+                    } else {
+                        row
+                    }
+                }),
+                [
+                    Constraint::Length(4),
+                    Constraint::Fill(1),
+                    Constraint::Length(8),
+                    Constraint::Length(5),
+                    Constraint::Length(9),
+                    Constraint::Length(9),
+                ],
+            )
+            .header(
+                Row::new(vec![
+                    "Rank", "Player", "Progress", "WPM", "Accuracy", "State",
+                ])
+                .style(theme.header),
             );
+            frame.render_widget(table, layout[1]);
+        }
+
+        let mut lines = self.help_lines(Screen::Race);
+        if race.is_connected && !race.is_host() {
+            lines.push(Line::styled(
+                "Waiting for the host to start the race",
+                theme.muted,
+            ));
+        }
+        if let Some(status_message) = &self.status_message {
+            lines.push(Line::styled(status_message.as_str(), theme.status));
         }
+        frame.render_widget(Paragraph::new(lines), layout[2]);
     }
+
+    /// Help text of `screen` followed by the status message, if any.
+    fn footer_lines(&self, screen: Screen) -> Vec<Line> {
+        let mut lines = self.help_lines(screen);
+        if let Some(status_message) = &self.status_message {
+            lines.push(Line::styled(
+                status_message.as_str(),
+                self.config.theme.status,
+            ));
+        }
+        lines
+    }
+
+    /// Help text of `screen`, generated from the keymap.
+    fn help_lines(&self, screen: Screen) -> Vec<Line> {
+        let theme = &self.config.theme;
+
+        self.config
+            .keymap
+            .help(screen, self.current_test.accepts_newlines())
+            .into_iter()
+            .map(|commands| {
+                commands
+                    .into_iter()
+                    .filter(|(command, _)| self.allows(command.action))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|commands| !commands.is_empty())
+            .map(|commands| {
+                let mut spans = Vec::new();
+                for (index, (command, keys)) in commands.into_iter().enumerate() {
+                    if index > 0 {
+                        spans.push(Span::from(", "));
+                    }
+                    for (key_index, key) in keys.iter().enumerate() {
+                        if key_index > 0 {
+                            spans.push(Span::from(if key_index + 1 == keys.len() {
+                                ", or "
+                            } else {
+                                ", "
+                            }));
+                        }
+                        spans.push(Span::styled(key.to_string(), theme.shortcut));
+                    }
+                    spans.push(Span::from(" - "));
+                    spans.push(Span::styled(
+                        match command.action {
+                            Action::NextTheme => {
+                                format!("{} ({})", command.description, theme.name)
+                            }
+                            _ => command.description.to_string(),
+                        },
+                        theme.action,
+                    ));
+                }
+                Line::from(spans)
+            })
+            .collect()
+    }
+}
+
+/// Number of rows `lines` take when wrapped between words to `width` columns, as
+/// [`Paragraph::wrap`] does.
+fn wrapped_height(lines: &[Line], width: u16) -> u16 {
+    let width = std::cmp::max(usize::from(width), 1);
+    let mut height = 0;
+    for line in lines {
+        let text: String = line
+            .spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect();
+        let mut row_width = 0;
+        height += 1;
+        for word in text.split(' ').map(|word| Span::raw(word).width()) {
+            if row_width > 0 && row_width + 1 + word > width {
+                height += 1;
+                row_width = 0;
+            }
+            row_width += usize::from(row_width > 0) + word;
+            // Words wider than a row are broken over several.
+            while row_width > width {
+                height += 1;
+                row_width -= width;
+            }
+        }
+    }
+    height as u16
+}
+
+/// Splits `graphemes` into rows of at most `width` columns, breaking lines at newlines and
+/// otherwise between words where possible. Returns the range of graphemes on every row; a newline
+/// ends its row and spaces may hang past the width, as they are invisible anyway.
+fn wrap_rows(graphemes: &[Span], width: usize) -> Vec<std::ops::Range<usize>> {
+    let width = std::cmp::max(width, 1);
+    let is_space = |index: usize| graphemes[index].content == " ";
+    let is_newline = |index: usize| graphemes[index].content == "\n";
+
+    let mut rows = Vec::new();
+    let mut row_start = 0;
+    let mut row_width = 0;
+    let mut index = 0;
+
+    while index < graphemes.len() {
+        if is_newline(index) {
+            rows.push(row_start..index + 1);
+            index += 1;
+            row_start = index;
+            row_width = 0;
+            continue;
+        }
+
+        let word_end = (index..graphemes.len())
+            .find(|&end| is_space(end) || is_newline(end))
+            .unwrap_or(graphemes.len());
+        let word_width: usize = graphemes[index..word_end].iter().map(Span::width).sum();
+
+        if row_width + word_width > width && row_width > 0 {
+            rows.push(row_start..index);
+            row_start = index;
+            row_width = 0;
+        }
+
+        if word_width > width {
+            // Too long for any row, the word is broken wherever the row is full.
+            for (grapheme_index, grapheme) in
+                graphemes.iter().enumerate().take(word_end).skip(index)
+            {
+                let grapheme_width = grapheme.width();
+                if row_width + grapheme_width > width && row_width > 0 {
+                    rows.push(row_start..grapheme_index);
+                    row_start = grapheme_index;
+                    row_width = 0;
+                }
+                row_width += grapheme_width;
+            }
+        } else {
+            row_width += word_width;
+        }
+        index = word_end;
+
+        while index < graphemes.len() && is_space(index) {
+            row_width += 1;
+            index += 1;
+        }
+    }
+
+    if row_start < graphemes.len() || rows.is_empty() {
+        rows.push(row_start..graphemes.len());
+    }
+
+    rows
 }